@@ -0,0 +1,237 @@
+use differ::Differ;
+use sequencematcher::Tag;
+
+const RESET: &str = "\x1b[0m";
+
+/// ANSI escape sequences used for each kind of line, an empty string leaves
+/// that kind uncoloured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Lines outside hunks, such as `---`, `+++` and `diff --git` lines.
+    pub file_header: String,
+    pub hunk_header: String,
+    pub context: String,
+    pub removed: String,
+    pub added: String,
+    /// Changed characters of a removed line that has a similar added line.
+    pub removed_highlight: String,
+    pub added_highlight: String,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::new()
+    }
+}
+
+impl Theme {
+    /// The colours of `git diff`, with changed characters in reverse video.
+    pub fn new() -> Theme {
+        Theme {
+            file_header: "\x1b[1m".to_string(),
+            hunk_header: "\x1b[36m".to_string(),
+            context: String::new(),
+            removed: "\x1b[31m".to_string(),
+            added: "\x1b[32m".to_string(),
+            removed_highlight: "\x1b[7;31m".to_string(),
+            added_highlight: "\x1b[7;32m".to_string(),
+        }
+    }
+}
+
+/// Colours unified diffs for terminals.
+pub struct ColorDiff {
+    pub theme: Theme,
+    /// When false `render` returns the lines untouched.
+    pub enabled: bool,
+    /// Pairs removed and added lines, the characters that differ between
+    /// similar lines are highlighted.
+    pub differ: Differ,
+}
+
+impl Default for ColorDiff {
+    fn default() -> ColorDiff {
+        ColorDiff::new()
+    }
+}
+
+impl ColorDiff {
+    pub fn new() -> ColorDiff {
+        ColorDiff {
+            theme: Theme::new(),
+            enabled: true,
+            differ: Differ::new(),
+        }
+    }
+
+    /// Colours the lines of a unified diff, such as the output of
+    /// `unified_diff`. Every line keeps its terminator, escape sequences go
+    /// before it.
+    pub fn render<S: AsRef<str>>(&self, lines: &[S]) -> Vec<String> {
+        if !self.enabled {
+            return lines.iter().map(|line| line.as_ref().to_string()).collect();
+        }
+        let mut res = Vec::with_capacity(lines.len());
+        // Lines of each side left in the current hunk.
+        let (mut from_left, mut to_left) = (0, 0);
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i].as_ref();
+            if from_left == 0 && to_left == 0 {
+                if let Some((from_count, to_count)) = hunk_counts(line) {
+                    from_left = from_count;
+                    to_left = to_count;
+                    res.push(paint(&self.theme.hunk_header, line));
+                } else if line.starts_with('\\') {
+                    res.push(paint(&self.theme.context, line));
+                } else {
+                    res.push(paint(&self.theme.file_header, line));
+                }
+                i += 1;
+            } else if line.starts_with('-') || line.starts_with('+') {
+                let start = i;
+                while i < lines.len() {
+                    let line = lines[i].as_ref();
+                    if line.starts_with('-') && from_left > 0 {
+                        from_left -= 1;
+                    } else if line.starts_with('+') && to_left > 0 {
+                        to_left -= 1;
+                    } else if !line.starts_with('\\') {
+                        break;
+                    }
+                    i += 1;
+                }
+                if i == start {
+                    // A change line for a side with no lines left, the hunk
+                    // doesn't match its header.
+                    from_left = 0;
+                    to_left = 0;
+                    continue;
+                }
+                self.render_changes(&mut res, &lines[start..i]);
+            } else if line.starts_with(' ') || line.starts_with('\\') || line == "\n" {
+                if !line.starts_with('\\') {
+                    from_left = from_left.saturating_sub(1);
+                    to_left = to_left.saturating_sub(1);
+                }
+                res.push(paint(&self.theme.context, line));
+                i += 1;
+            } else {
+                // The hunk is shorter than its header says.
+                from_left = 0;
+                to_left = 0;
+            }
+        }
+        res
+    }
+
+    fn render_changes<S: AsRef<str>>(&self, res: &mut Vec<String>, lines: &[S]) {
+        let removed: Vec<&str> = lines
+            .iter()
+            .map(|line| line.as_ref())
+            .filter(|line| line.starts_with('-'))
+            .map(|line| &line[1..])
+            .collect();
+        let added: Vec<&str> = lines
+            .iter()
+            .map(|line| line.as_ref())
+            .filter(|line| line.starts_with('+'))
+            .map(|line| &line[1..])
+            .collect();
+        let mut removed_ranges = vec![Vec::new(); removed.len()];
+        let mut added_ranges = vec![Vec::new(); added.len()];
+        for diff in self.differ.intraline_diffs(&removed, &added) {
+            for change in diff.changes {
+                if change.tag != Tag::Insert {
+                    removed_ranges[diff.first_line].push((change.first_start, change.first_end));
+                }
+                if change.tag != Tag::Delete {
+                    added_ranges[diff.second_line].push((change.second_start, change.second_end));
+                }
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        for line in lines {
+            let line = line.as_ref();
+            if line.starts_with('-') {
+                res.push(paint_ranges(
+                    &self.theme.removed,
+                    &self.theme.removed_highlight,
+                    line,
+                    &removed_ranges[i],
+                ));
+                i += 1;
+            } else if line.starts_with('+') {
+                res.push(paint_ranges(
+                    &self.theme.added,
+                    &self.theme.added_highlight,
+                    line,
+                    &added_ranges[j],
+                ));
+                j += 1;
+            } else {
+                res.push(paint(&self.theme.context, line));
+            }
+        }
+    }
+}
+
+// Line counts of both sides from a `@@ -1,3 +1,4 @@` header.
+fn hunk_counts(line: &str) -> Option<(usize, usize)> {
+    let mut parts = line.split(' ');
+    if parts.next() != Some("@@") {
+        return None;
+    }
+    let count = |range: Option<&str>, sign: char| -> Option<usize> {
+        let range = range?.strip_prefix(sign)?;
+        match range.find(',') {
+            Some(comma) => range[comma + 1..].parse().ok(),
+            None => range.parse::<usize>().ok().map(|_| 1),
+        }
+    };
+    let from_count = count(parts.next(), '-')?;
+    let to_count = count(parts.next(), '+')?;
+    Some((from_count, to_count))
+}
+
+fn split_newline(line: &str) -> (&str, &str) {
+    match line.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (line, ""),
+    }
+}
+
+fn paint(style: &str, line: &str) -> String {
+    paint_ranges(style, "", line, &[])
+}
+
+// Paints a line in `style` and the characters within `ranges`, indices into
+// the line without its one character prefix, in `highlight`.
+fn paint_ranges(style: &str, highlight: &str, line: &str, ranges: &[(usize, usize)]) -> String {
+    let (body, newline) = split_newline(line);
+    let ranges = if highlight.is_empty() { &[] } else { ranges };
+    if style.is_empty() && ranges.is_empty() {
+        return line.to_string();
+    }
+    let mut res = String::from(style);
+    let mut highlighted = false;
+    for (index, c) in body.chars().enumerate() {
+        let inside = index > 0
+            && ranges
+                .iter()
+                .any(|&(start, end)| start < index && index <= end);
+        if inside != highlighted {
+            if inside {
+                res.push_str(highlight);
+            } else {
+                res.push_str(RESET);
+                res.push_str(style);
+            }
+            highlighted = inside;
+        }
+        res.push(c);
+    }
+    res.push_str(RESET);
+    res.push_str(newline);
+    res
+}
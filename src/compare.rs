@@ -0,0 +1,154 @@
+#[cfg(feature = "regex")]
+use regex::Regex;
+use sequencematcher::Opcode;
+use std::fmt::Display;
+
+/// Differences to overlook when matching lines, like the options of the same
+/// names in `diff`. Lines are only normalised for matching, diffs still show
+/// them as they are.
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// Ignore all white space within lines, `diff -w`.
+    pub ignore_all_space: bool,
+    /// Ignore changes in the amount of white space and white space at the
+    /// end of lines, `diff -b`.
+    pub ignore_space_change: bool,
+    /// Ignore case, `diff -i`.
+    pub ignore_case: bool,
+    /// Ignore changes that only insert or delete blank lines,
+    /// `diff --ignore-blank-lines`.
+    pub ignore_blank_lines: bool,
+    /// Ignore changes whose inserted and deleted lines all match one of the
+    /// patterns, or are blank with `ignore_blank_lines`, `diff -I`. Patterns
+    /// are matched against lines without their newline. Only with the
+    /// `regex` feature.
+    #[cfg(feature = "regex")]
+    pub ignore_matching_lines: Vec<Regex>,
+    /// Treat a line ending in `\r\n` like one ending in `\n`,
+    /// `diff --strip-trailing-cr`.
+    pub strip_trailing_cr: bool,
+}
+
+impl CompareOptions {
+    pub fn new() -> CompareOptions {
+        CompareOptions::default()
+    }
+
+    /// True when lines have to be normalised before they are matched.
+    pub fn normalizes(&self) -> bool {
+        self.ignore_all_space
+            || self.ignore_space_change
+            || self.ignore_case
+            || self.strip_trailing_cr
+    }
+
+    /// The form of `line` that is matched against other lines.
+    pub fn normalize(&self, line: &str) -> String {
+        let (mut body, newline) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        if self.strip_trailing_cr {
+            body = body.strip_suffix('\r').unwrap_or(body);
+        }
+        let lowered;
+        if self.ignore_case {
+            lowered = body.to_lowercase();
+            body = &lowered;
+        }
+        let mut res = String::with_capacity(line.len());
+        if self.ignore_all_space {
+            res.extend(body.chars().filter(|c| !c.is_whitespace()));
+        } else if self.ignore_space_change {
+            for (i, word) in body.split_whitespace().enumerate() {
+                if i > 0 || body.starts_with(char::is_whitespace) {
+                    res.push(' ');
+                }
+                res.push_str(word);
+            }
+        } else {
+            res.push_str(body);
+        }
+        res.push_str(newline);
+        res
+    }
+
+    pub(crate) fn normalize_all<T: Display>(&self, sequence: &[T]) -> Vec<String> {
+        sequence
+            .iter()
+            .map(|item| self.normalize(&item.to_string()))
+            .collect()
+    }
+
+    /// True when changes made up of `line` alone are left out of diffs.
+    pub fn is_ignored_line(&self, line: &str) -> bool {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        (self.ignore_blank_lines && is_blank(line)) || self.matches_pattern(line)
+    }
+
+    #[cfg(feature = "regex")]
+    fn matches_pattern(&self, line: &str) -> bool {
+        self.ignore_matching_lines
+            .iter()
+            .any(|regex| regex.is_match(line))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn matches_pattern(&self, _line: &str) -> bool {
+        false
+    }
+
+    // `Regex` has no `PartialEq`, patterns are compared by their source text.
+    #[cfg(feature = "regex")]
+    fn same_patterns(&self, other: &CompareOptions) -> bool {
+        self.ignore_matching_lines
+            .iter()
+            .map(Regex::as_str)
+            .eq(other.ignore_matching_lines.iter().map(Regex::as_str))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn same_patterns(&self, _other: &CompareOptions) -> bool {
+        true
+    }
+
+    /// True when some lines are left out of diffs, see `is_ignored_line`.
+    pub fn ignores_lines(&self) -> bool {
+        #[cfg(feature = "regex")]
+        let has_patterns = !self.ignore_matching_lines.is_empty();
+        #[cfg(not(feature = "regex"))]
+        let has_patterns = false;
+        self.ignore_blank_lines || has_patterns
+    }
+
+    // True when every line `opcode` inserts or deletes is ignored.
+    pub(crate) fn is_ignored<T: Display>(
+        &self,
+        opcode: &Opcode,
+        first_sequence: &[T],
+        second_sequence: &[T],
+    ) -> bool {
+        self.ignores_lines()
+            && first_sequence[opcode.first_start..opcode.first_end]
+                .iter()
+                .chain(&second_sequence[opcode.second_start..opcode.second_end])
+                .all(|item| self.is_ignored_line(&item.to_string()))
+    }
+}
+
+impl PartialEq for CompareOptions {
+    fn eq(&self, other: &CompareOptions) -> bool {
+        self.ignore_all_space == other.ignore_all_space
+            && self.ignore_space_change == other.ignore_space_change
+            && self.ignore_case == other.ignore_case
+            && self.ignore_blank_lines == other.ignore_blank_lines
+            && self.same_patterns(other)
+            && self.strip_trailing_cr == other.strip_trailing_cr
+    }
+}
+
+impl Eq for CompareOptions {}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
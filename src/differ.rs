@@ -1,11 +1,63 @@
-use sequencematcher::SequenceMatcher;
-use std::cmp;
-use utils::{count_leading, str_with_similar_chars};
+use compare::CompareOptions;
+use sequencematcher::{Algorithm, Opcode, SequenceMatcher, Tag};
+use utils::{keep_original_whitespace, str_with_similar_chars};
+
+pub type LineJunk = Box<dyn Fn(&&str) -> bool + Send + Sync>;
+pub type CharJunk = Box<dyn Fn(&char) -> bool + Send + Sync>;
+
+/// A line of `Differ` output, holding indices into the compared sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineOp {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+    /// Similar lines, written by `compare` with `?` lines between them.
+    Replace(usize, usize),
+}
+
+/// Changed characters of a pair of similar lines, see `Differ::intraline_diffs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntralineDiff {
+    /// Index of the line in each sequence.
+    pub first_line: usize,
+    pub second_line: usize,
+    /// Replaced, deleted and inserted ranges as char indices into the lines,
+    /// the `?` lines of `compare` mark the same ranges.
+    pub changes: Vec<Opcode>,
+}
+
+impl IntralineDiff {
+    /// `changes` with byte offsets instead of char indices, for slicing the
+    /// two lines.
+    pub fn byte_changes(&self, first_line: &str, second_line: &str) -> Vec<Opcode> {
+        let first_offsets = char_offsets(first_line);
+        let second_offsets = char_offsets(second_line);
+        self.changes
+            .iter()
+            .map(|change| Opcode {
+                tag: change.tag,
+                first_start: first_offsets[change.first_start],
+                first_end: first_offsets[change.first_end],
+                second_start: second_offsets[change.second_start],
+                second_end: second_offsets[change.second_end],
+            })
+            .collect()
+    }
+}
 
 #[derive(Default)]
 pub struct Differ {
-    pub line_junk: Option<fn(&&str) -> bool>,
-    pub char_junk: Option<fn(&char) -> bool>,
+    pub line_junk: Option<LineJunk>,
+    pub char_junk: Option<CharJunk>,
+    /// Algorithm used to match lines, characters within similar lines are
+    /// always matched with `Algorithm::RatcliffObershelp`.
+    pub algorithm: Algorithm,
+    /// Lines that are the same once normalised are written as common lines,
+    /// taken from the first sequence. Changes made up only of lines ignored
+    /// with `ignore_blank_lines` or `ignore_matching_lines` are left out,
+    /// like in `unified_diff_with_options`, so neither side's lines of such
+    /// a change are written.
+    pub compare: CompareOptions,
 }
 
 impl Differ {
@@ -13,93 +65,140 @@ impl Differ {
         Differ {
             line_junk: None,
             char_junk: None,
+            algorithm: Algorithm::default(),
+            compare: CompareOptions::new(),
         }
     }
 
     pub fn compare(&self, first_sequence: &[&str], second_sequence: &[&str]) -> Vec<String> {
-        let mut matcher = SequenceMatcher::new(first_sequence, second_sequence);
-        matcher.set_is_junk(self.line_junk);
-        let mut res = Vec::new();
-        for opcode in matcher.get_opcodes() {
-            let mut gen = Vec::new();
-            match opcode.tag.as_ref() {
-                "replace" => {
-                    gen = self.fancy_replace(
-                        first_sequence,
-                        opcode.first_start,
-                        opcode.first_end,
-                        second_sequence,
-                        opcode.second_start,
-                        opcode.second_end,
-                    )
-                }
-                "delete" => {
-                    gen = self.dump("-", first_sequence, opcode.first_start, opcode.first_end)
-                }
-                "insert" => {
-                    gen = self.dump("+", second_sequence, opcode.second_start, opcode.second_end)
-                }
-                "equal" => {
-                    gen = self.dump(" ", first_sequence, opcode.first_start, opcode.first_end)
-                }
-                _ => {}
+        self.compare_iter(first_sequence, second_sequence).collect()
+    }
+
+    pub fn compare_iter<'a>(
+        &'a self,
+        first_sequence: &'a [&'a str],
+        second_sequence: &'a [&'a str],
+    ) -> impl Iterator<Item = String> + 'a {
+        self.line_ops_iter(first_sequence, second_sequence)
+            .flat_map(move |op| match op {
+                LineOp::Equal(i, _) => vec![format!("  {}", first_sequence[i])],
+                LineOp::Delete(i) => vec![format!("- {}", first_sequence[i])],
+                LineOp::Insert(j) => vec![format!("+ {}", second_sequence[j])],
+                LineOp::Replace(i, j) => self.replace_lines(first_sequence[i], second_sequence[j]),
+            })
+    }
+
+    /// Lines of both sequences in the order `compare` writes them.
+    pub fn line_ops(&self, first_sequence: &[&str], second_sequence: &[&str]) -> Vec<LineOp> {
+        self.line_ops_iter(first_sequence, second_sequence).collect()
+    }
+
+    /// Changed characters of every pair of similar lines `compare` writes
+    /// with `?` lines, in the same order.
+    pub fn intraline_diffs(
+        &self,
+        first_sequence: &[&str],
+        second_sequence: &[&str],
+    ) -> Vec<IntralineDiff> {
+        self.line_ops_iter(first_sequence, second_sequence)
+            .filter_map(|op| match op {
+                LineOp::Replace(i, j) => Some(IntralineDiff {
+                    first_line: i,
+                    second_line: j,
+                    changes: self
+                        .char_opcodes(first_sequence[i], second_sequence[j])
+                        .into_iter()
+                        .filter(|opcode| opcode.tag != Tag::Equal)
+                        .collect(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn line_ops_iter<'a>(
+        &'a self,
+        first_sequence: &'a [&'a str],
+        second_sequence: &'a [&'a str],
+    ) -> impl Iterator<Item = LineOp> + 'a {
+        let opcodes = if self.compare.normalizes() {
+            let first_keys = self.compare.normalize_all(first_sequence);
+            let second_keys = self.compare.normalize_all(second_sequence);
+            let first_keys: Vec<&str> = first_keys.iter().map(|key| key.as_str()).collect();
+            let second_keys: Vec<&str> = second_keys.iter().map(|key| key.as_str()).collect();
+            self.line_opcodes(&first_keys, &second_keys)
+        } else {
+            self.line_opcodes(first_sequence, second_sequence)
+        };
+        opcodes.into_iter().flat_map(move |opcode| {
+            let mut res = Vec::new();
+            if opcode.tag != Tag::Equal
+                && self
+                    .compare
+                    .is_ignored(&opcode, first_sequence, second_sequence)
+            {
+                return res;
             }
-            for i in gen {
-                res.push(i);
+            match opcode.tag {
+                Tag::Replace => self.fancy_replace(
+                    &mut res,
+                    first_sequence,
+                    opcode.first_start,
+                    opcode.first_end,
+                    second_sequence,
+                    opcode.second_start,
+                    opcode.second_end,
+                ),
+                Tag::Delete => dump_first(&mut res, opcode.first_start, opcode.first_end),
+                Tag::Insert => dump_second(&mut res, opcode.second_start, opcode.second_end),
+                Tag::Equal => {
+                    let pairs = (opcode.first_start..opcode.first_end)
+                        .zip(opcode.second_start..opcode.second_end);
+                    res.extend(pairs.map(|(i, j)| LineOp::Equal(i, j)));
+                }
             }
-        }
-        res
+            res
+        })
     }
 
-    fn dump(&self, tag: &str, sequence: &[&str], start: usize, end: usize) -> Vec<String> {
-        let mut res = Vec::new();
-        for i in start..end {
-            if let Some(s) = sequence.get(i) {
-                res.push(format!("{} {}", tag, s))
-            }
-        }
-        res
+    fn line_opcodes(&self, first_sequence: &[&str], second_sequence: &[&str]) -> Vec<Opcode> {
+        let mut matcher = SequenceMatcher::new(first_sequence, second_sequence);
+        matcher.set_is_junk(self.line_junk.as_ref());
+        matcher.set_algorithm(self.algorithm);
+        matcher.get_opcodes()
     }
 
     fn plain_replace(
         &self,
-        first_sequence: &[&str],
+        res: &mut Vec<LineOp>,
         first_start: usize,
         first_end: usize,
-        second_sequence: &[&str],
         second_start: usize,
         second_end: usize,
-    ) -> Vec<String> {
+    ) {
         if !(first_start < first_end && second_start < second_end) {
-            return Vec::new();
+            return;
         }
-        let (mut first, second) = if second_end - second_start < first_end - first_start {
-            (
-                self.dump("+", second_sequence, second_start, second_end),
-                self.dump("-", first_sequence, first_start, first_end),
-            )
+        if second_end - second_start < first_end - first_start {
+            dump_second(res, second_start, second_end);
+            dump_first(res, first_start, first_end);
         } else {
-            (
-                self.dump("-", first_sequence, first_start, first_end),
-                self.dump("+", second_sequence, second_start, second_end),
-            )
-        };
-        for s in second {
-            first.push(s);
+            dump_first(res, first_start, first_end);
+            dump_second(res, second_start, second_end);
         }
-        first
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn fancy_replace(
         &self,
+        res: &mut Vec<LineOp>,
         first_sequence: &[&str],
         first_start: usize,
         first_end: usize,
         second_sequence: &[&str],
         second_start: usize,
         second_end: usize,
-    ) -> Vec<String> {
-        let mut res = Vec::new();
+    ) {
         let (mut best_ratio, cutoff) = (0.74, 0.75);
         let (mut best_i, mut best_j) = (0, 0);
         let mut eqi: Option<usize> = None;
@@ -113,10 +212,10 @@ impl Differ {
             for (i, first_sequence_str) in first_sequence
                 .iter()
                 .enumerate()
-                .take(second_end)
-                .skip(second_start)
+                .take(first_end)
+                .skip(first_start)
             {
-                if first_sequence_str == second_sequence_str {
+                if self.same_line(first_sequence_str, second_sequence_str) {
                     if eqi.is_none() {
                         eqi = Some(i);
                         eqj = Some(j);
@@ -129,8 +228,13 @@ impl Differ {
                 );
                 let mut cruncher =
                     SequenceMatcher::new(&first_sequence_chars, &second_sequence_chars);
-                cruncher.set_is_junk(self.char_junk);
-                if cruncher.ratio() > best_ratio {
+                cruncher.set_is_junk(self.char_junk.as_ref());
+                // Computing the full ratio is expensive, so try the cheap
+                // upper bounds first.
+                if cruncher.real_quick_ratio() > best_ratio
+                    && cruncher.quick_ratio() > best_ratio
+                    && cruncher.ratio() > best_ratio
+                {
                     best_ratio = cruncher.ratio();
                     best_i = i;
                     best_j = j;
@@ -139,103 +243,54 @@ impl Differ {
         }
         if best_ratio < cutoff {
             if eqi.is_none() {
-                res.extend(
-                    self.plain_replace(
-                        first_sequence,
-                        first_start,
-                        first_end,
-                        second_sequence,
-                        second_start,
-                        second_end,
-                    ).iter()
-                        .cloned(),
-                );
-                return res;
+                self.plain_replace(res, first_start, first_end, second_start, second_end);
+                return;
             }
             best_i = eqi.unwrap();
             best_j = eqj.unwrap();
         } else {
             eqi = None;
         }
-        res.extend(
-            self.fancy_helper(
-                first_sequence,
-                first_start,
-                best_i,
-                second_sequence,
-                second_start,
-                best_j,
-            ).iter()
-                .cloned(),
+        self.fancy_helper(
+            res,
+            first_sequence,
+            first_start,
+            best_i,
+            second_sequence,
+            second_start,
+            best_j,
         );
-        let first_element = &first_sequence[best_i];
-        let second_element = &second_sequence[best_j];
         if eqi.is_none() {
-            let (mut first_tag, mut second_tag) = (String::new(), String::new());
-            let first_element_chars: Vec<char> = first_element.chars().collect();
-            let second_element_chars: Vec<char> = second_element.chars().collect();
-            let mut cruncher = SequenceMatcher::new(&first_element_chars, &second_element_chars);
-            cruncher.set_is_junk(self.char_junk);
-            for opcode in &cruncher.get_opcodes() {
-                let (first_length, second_length) = (
-                    opcode.first_end - opcode.first_start,
-                    opcode.second_end - opcode.second_start,
-                );
-                match opcode.tag.as_ref() {
-                    "replace" => {
-                        first_tag.push_str(&str_with_similar_chars('^', first_length));
-                        second_tag.push_str(&str_with_similar_chars('^', second_length));
-                    }
-                    "delete" => {
-                        first_tag.push_str(&str_with_similar_chars('-', first_length));
-                    }
-                    "insert" => {
-                        second_tag.push_str(&str_with_similar_chars('+', second_length));
-                    }
-                    "equal" => {
-                        first_tag.push_str(&str_with_similar_chars(' ', first_length));
-                        second_tag.push_str(&str_with_similar_chars(' ', second_length));
-                    }
-                    _ => {}
-                }
-            }
-            res.extend(
-                self.qformat(&first_element, &second_element, &first_tag, &second_tag)
-                    .iter()
-                    .cloned(),
-            );
+            res.push(LineOp::Replace(best_i, best_j));
         } else {
-            let mut s = String::from("  ");
-            s.push_str(&first_element);
-            res.push(s);
+            res.push(LineOp::Equal(best_i, best_j));
         }
-        res.extend(
-            self.fancy_helper(
-                first_sequence,
-                best_i + 1,
-                first_end,
-                second_sequence,
-                best_j + 1,
-                second_end,
-            ).iter()
-                .cloned(),
+        self.fancy_helper(
+            res,
+            first_sequence,
+            best_i + 1,
+            first_end,
+            second_sequence,
+            best_j + 1,
+            second_end,
         );
-        res
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn fancy_helper(
         &self,
+        res: &mut Vec<LineOp>,
         first_sequence: &[&str],
         first_start: usize,
         first_end: usize,
         second_sequence: &[&str],
         second_start: usize,
         second_end: usize,
-    ) -> Vec<String> {
-        let mut res = Vec::new();
+    ) {
         if first_start < first_end {
             if second_start < second_end {
-                res = self.fancy_replace(
+                self.fancy_replace(
+                    res,
                     first_sequence,
                     first_start,
                     first_end,
@@ -244,12 +299,54 @@ impl Differ {
                     second_end,
                 );
             } else {
-                res = self.dump("-", first_sequence, first_start, first_end);
+                dump_first(res, first_start, first_end);
             }
         } else if second_start < second_end {
-            res = self.dump("+", second_sequence, second_start, second_end);
+            dump_second(res, second_start, second_end);
         }
-        res
+    }
+
+    fn same_line(&self, first_line: &str, second_line: &str) -> bool {
+        first_line == second_line
+            || (self.compare.normalizes()
+                && self.compare.normalize(first_line) == self.compare.normalize(second_line))
+    }
+
+    // Character level comparison of two similar lines.
+    pub(crate) fn char_opcodes(&self, first_line: &str, second_line: &str) -> Vec<Opcode> {
+        let first_chars: Vec<char> = first_line.chars().collect();
+        let second_chars: Vec<char> = second_line.chars().collect();
+        let mut cruncher = SequenceMatcher::new(&first_chars, &second_chars);
+        cruncher.set_is_junk(self.char_junk.as_ref());
+        cruncher.get_opcodes()
+    }
+
+    // Writes a pair of similar lines with `?` lines marking the differences.
+    fn replace_lines(&self, first_element: &str, second_element: &str) -> Vec<String> {
+        let (mut first_tag, mut second_tag) = (String::new(), String::new());
+        for opcode in &self.char_opcodes(first_element, second_element) {
+            let (first_length, second_length) = (
+                opcode.first_end - opcode.first_start,
+                opcode.second_end - opcode.second_start,
+            );
+            match opcode.tag {
+                Tag::Replace => {
+                    first_tag.push_str(&str_with_similar_chars('^', first_length));
+                    second_tag.push_str(&str_with_similar_chars('^', second_length));
+                }
+                Tag::Delete => {
+                    first_tag.push_str(&str_with_similar_chars('-', first_length));
+                }
+                Tag::Insert => {
+                    second_tag.push_str(&str_with_similar_chars('+', second_length));
+                }
+                Tag::Equal => {
+                    first_tag.push_str(&str_with_similar_chars(' ', first_length));
+                    second_tag.push_str(&str_with_similar_chars(' ', second_length));
+                }
+            }
+        }
+        self.qformat(first_element, second_element, &first_tag, &second_tag)
     }
 
     fn qformat(
@@ -260,31 +357,17 @@ impl Differ {
         second_tags: &str,
     ) -> Vec<String> {
         let mut res = Vec::new();
-        let mut first_tags = first_tags;
-        let mut second_tags = second_tags;
-        let mut common = cmp::min(
-            count_leading(first_line, '\t'),
-            count_leading(second_line, '\t'),
-        );
-        common = cmp::min(common, count_leading(first_tags.split_at(common).0, ' '));
-        common = cmp::min(common, count_leading(first_tags.split_at(common).0, ' '));
-        first_tags = first_tags.split_at(common).1.trim_right();
-        second_tags = second_tags.split_at(common).1.trim_right();
-        let mut s = format!("- {}", first_line);
-        res.push(s);
-        if first_tags != "" {
-            s = format!("? {}{}\n", str_with_similar_chars('\t', common), first_tags);
-            res.push(s);
+        let first_tags = keep_original_whitespace(first_line, first_tags);
+        let second_tags = keep_original_whitespace(second_line, second_tags);
+        let first_tags = first_tags.trim_end();
+        let second_tags = second_tags.trim_end();
+        res.push(format!("- {}", first_line));
+        if !first_tags.is_empty() {
+            res.push(format!("? {}\n", first_tags));
         }
-        s = format!("+ {}", second_line);
-        res.push(s);
-        if second_tags != "" {
-            s = format!(
-                "? {}{}\n",
-                str_with_similar_chars('\t', common),
-                second_tags
-            );
-            res.push(s);
+        res.push(format!("+ {}", second_line));
+        if !second_tags.is_empty() {
+            res.push(format!("? {}\n", second_tags));
         }
         res
     }
@@ -305,13 +388,94 @@ impl Differ {
         }
         res
     }
+
+    /// Turns a delta from `compare` into one going the other way, so that
+    /// `restore(&invert(delta), 1) == restore(delta, 2)` and vice versa.
+    /// Pairs of similar lines keep the `-` line and its `?` line in front of
+    /// the `+` line, as `compare` writes them.
+    pub fn invert(delta: &[String]) -> Vec<String> {
+        let lines: Vec<String> = delta
+            .iter()
+            .map(|line| {
+                if let Some(text) = line.strip_prefix("- ") {
+                    format!("+ {}", text)
+                } else if let Some(text) = line.strip_prefix("+ ") {
+                    format!("- {}", text)
+                } else if line.starts_with("? ") {
+                    line.chars()
+                        .map(|c| match c {
+                            '-' => '+',
+                            '+' => '-',
+                            _ => c,
+                        })
+                        .collect()
+                } else {
+                    line.clone()
+                }
+            })
+            .collect();
+        // Lines together with the `?` line following them.
+        let mut units = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let end = if lines.get(i + 1).is_some_and(|next| next.starts_with("? ")) {
+                i + 2
+            } else {
+                i + 1
+            };
+            units.push(&lines[i..end]);
+            i = end;
+        }
+        // A `+` line directly followed by a `-` line is a pair of similar
+        // lines when either has a `?` line, plain runs are left in order.
+        let mut res = Vec::with_capacity(lines.len());
+        let mut k = 0;
+        while k < units.len() {
+            match units.get(k + 1) {
+                Some(next)
+                    if units[k][0].starts_with("+ ")
+                        && next[0].starts_with("- ")
+                        && (units[k].len() == 2 || next.len() == 2) =>
+                {
+                    res.extend_from_slice(next);
+                    res.extend_from_slice(units[k]);
+                    k += 2;
+                }
+                _ => {
+                    res.extend_from_slice(units[k]);
+                    k += 1;
+                }
+            }
+        }
+        res
+    }
+}
+
+fn dump_first(res: &mut Vec<LineOp>, start: usize, end: usize) {
+    res.extend((start..end).map(LineOp::Delete));
+}
+
+fn dump_second(res: &mut Vec<LineOp>, start: usize, end: usize) {
+    res.extend((start..end).map(LineOp::Insert));
+}
+
+// Byte offset of every char of `line`, followed by the length of the line.
+fn char_offsets(line: &str) -> Vec<usize> {
+    line.char_indices()
+        .map(|(offset, _)| offset)
+        .chain(Some(line.len()))
+        .collect()
 }
 
 #[test]
 fn test_fancy_replace() {
     let differ = Differ::new();
+    let (first_sequence, second_sequence) = (["abcDefghiJkl\n"], ["abcdefGhijkl\n"]);
+    let mut ops = Vec::new();
+    differ.fancy_replace(&mut ops, &first_sequence, 0, 1, &second_sequence, 0, 1);
+    assert_eq!(ops, vec![LineOp::Replace(0, 0)]);
     let result = differ
-        .fancy_replace(&vec!["abcDefghiJkl\n"], 0, 1, &vec!["abcdefGhijkl\n"], 0, 1)
+        .replace_lines(first_sequence[0], second_sequence[0])
         .join("");
     assert_eq!(
         result,
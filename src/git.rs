@@ -0,0 +1,210 @@
+use sequencematcher::SequenceMatcher;
+use unified_diff_with_options;
+use DiffOptions;
+
+pub const REGULAR_FILE_MODE: u32 = 0o100644;
+pub const EXECUTABLE_FILE_MODE: u32 = 0o100755;
+
+const NULL_PATH: &str = "/dev/null";
+
+/// One side of a git diff.
+#[derive(Debug, Clone, Copy)]
+pub struct GitFile<'a> {
+    pub path: &'a str,
+    pub mode: u32,
+    pub lines: &'a [&'a str],
+}
+
+impl<'a> GitFile<'a> {
+    /// A regular, non-executable file.
+    pub fn new(path: &'a str, lines: &'a [&'a str]) -> GitFile<'a> {
+        GitFile {
+            path,
+            mode: REGULAR_FILE_MODE,
+            lines,
+        }
+    }
+}
+
+/// Settings for `diff`.
+#[derive(Debug, Clone)]
+pub struct GitOptions {
+    /// Context size, algorithm and line terminator of the hunks.
+    pub diff: DiffOptions,
+    /// Write full 40 character blob hashes on `index` lines instead of 7.
+    pub full_index: bool,
+    pub src_prefix: String,
+    pub dst_prefix: String,
+    /// Lowest similarity index, in percent, at which a change of path is
+    /// written as a rename, like `git diff -M`. Less similar files are
+    /// written as a deleted file followed by a new one.
+    pub rename_threshold: usize,
+}
+
+impl Default for GitOptions {
+    fn default() -> GitOptions {
+        GitOptions::new()
+    }
+}
+
+impl GitOptions {
+    pub fn new() -> GitOptions {
+        GitOptions {
+            diff: DiffOptions::new(),
+            full_index: false,
+            src_prefix: "a/".to_string(),
+            dst_prefix: "b/".to_string(),
+            rename_threshold: 50,
+        }
+    }
+}
+
+/// Formats the change from `old` to `new` the way `git diff` does, `None`
+/// standing for a file that doesn't exist on that side. A change of path is
+/// written as a rename when the similarity index, taken from
+/// `SequenceMatcher::ratio`, reaches `GitOptions::rename_threshold`. Returns
+/// nothing when the files are the same.
+pub fn diff(old: Option<&GitFile>, new: Option<&GitFile>, options: &GitOptions) -> Vec<String> {
+    let (old, new) = match (old, new) {
+        (None, None) => return Vec::new(),
+        (Some(old), None) => (
+            *old,
+            GitFile {
+                mode: 0,
+                lines: &[],
+                ..*old
+            },
+        ),
+        (None, Some(new)) => (
+            GitFile {
+                mode: 0,
+                lines: &[],
+                ..*new
+            },
+            *new,
+        ),
+        (Some(old), Some(new)) => (*old, *new),
+    };
+    let lineterm = &options.diff.lineterm;
+    let old_hash = blob_hash(old.lines, old.mode, options.full_index);
+    let new_hash = blob_hash(new.lines, new.mode, options.full_index);
+    let renamed = old.path != new.path;
+    if !renamed && old.mode == new.mode && old_hash == new_hash {
+        return Vec::new();
+    }
+    let mut similarity = 0;
+    if renamed {
+        similarity = (SequenceMatcher::new(old.lines, new.lines).ratio() * 100.0) as usize;
+        if similarity < options.rename_threshold {
+            let mut res = diff(Some(&old), None, options);
+            res.extend(diff(None, Some(&new), options));
+            return res;
+        }
+    }
+
+    let mut res = vec![format!(
+        "diff --git {}{} {}{}{}",
+        options.src_prefix, old.path, options.dst_prefix, new.path, lineterm
+    )];
+    if old.mode == 0 {
+        res.push(format!("new file mode {:06o}{}", new.mode, lineterm));
+    } else if new.mode == 0 {
+        res.push(format!("deleted file mode {:06o}{}", old.mode, lineterm));
+    } else if old.mode != new.mode {
+        res.push(format!("old mode {:06o}{}", old.mode, lineterm));
+        res.push(format!("new mode {:06o}{}", new.mode, lineterm));
+    }
+    if renamed {
+        res.push(format!("similarity index {}%{}", similarity, lineterm));
+        res.push(format!("rename from {}{}", old.path, lineterm));
+        res.push(format!("rename to {}{}", new.path, lineterm));
+    }
+    if old_hash == new_hash {
+        return res;
+    }
+    if old.mode == new.mode {
+        res.push(format!(
+            "index {}..{} {:06o}{}",
+            old_hash, new_hash, old.mode, lineterm
+        ));
+    } else {
+        res.push(format!("index {}..{}{}", old_hash, new_hash, lineterm));
+    }
+    let from_file = match old.mode {
+        0 => NULL_PATH.to_string(),
+        _ => format!("{}{}", options.src_prefix, old.path),
+    };
+    let to_file = match new.mode {
+        0 => NULL_PATH.to_string(),
+        _ => format!("{}{}", options.dst_prefix, new.path),
+    };
+    let mut hunks =
+        unified_diff_with_options(old.lines, new.lines, "", "", "", "", &options.diff).skip(2);
+    if let Some(first) = hunks.next() {
+        res.push(format!("--- {}{}", from_file, lineterm));
+        res.push(format!("+++ {}{}", to_file, lineterm));
+        res.push(first);
+        res.extend(hunks);
+    }
+    res
+}
+
+// Object id git gives the file contents, all zeros for a missing file.
+fn blob_hash(lines: &[&str], mode: u32, full: bool) -> String {
+    let length = if full { 40 } else { 7 };
+    if mode == 0 {
+        return "0".repeat(length);
+    }
+    let content = lines.concat();
+    let mut data = format!("blob {}\0", content.len()).into_bytes();
+    data.extend_from_slice(content.as_bytes());
+    let hash: String = sha1(&data).iter().map(|b| format!("{:02x}", b)).collect();
+    hash[..length].to_string()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip(&[a, b, c, d, e]) {
+            *value = value.wrapping_add(*add);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (i, value) in state.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
@@ -0,0 +1,174 @@
+use myers;
+use sequencematcher::Match;
+use std::collections::HashMap;
+use std::hash::Hash;
+use utils::{common_prefix_len, common_suffix_len};
+
+// Elements occurring more often than this in the first sequence are never
+// used to anchor a region.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Returns the blocks of equal elements found by histogram diff: the common
+/// region built around the least frequent elements of the first sequence
+/// splits the problem and both sides are diffed recursively. Ranges where
+/// every common element is too frequent fall back to Myers' algorithm.
+/// Blocks are ordered but not merged and carry no sentinel.
+pub fn matching_blocks<T: Hash + Eq>(first_sequence: &[T], second_sequence: &[T]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    histogram(
+        first_sequence,
+        0,
+        first_sequence.len(),
+        second_sequence,
+        0,
+        second_sequence.len(),
+        &mut matches,
+    );
+    matches
+}
+
+fn histogram<T: Hash + Eq>(
+    first_sequence: &[T],
+    mut first_start: usize,
+    mut first_end: usize,
+    second_sequence: &[T],
+    mut second_start: usize,
+    mut second_end: usize,
+    matches: &mut Vec<Match>,
+) {
+    let prefix = common_prefix_len(
+        &first_sequence[first_start..first_end],
+        &second_sequence[second_start..second_end],
+    );
+    if prefix > 0 {
+        matches.push(Match {
+            first_start,
+            second_start,
+            size: prefix,
+        });
+    }
+    first_start += prefix;
+    second_start += prefix;
+    let suffix = common_suffix_len(
+        &first_sequence[first_start..first_end],
+        &second_sequence[second_start..second_end],
+    );
+    first_end -= suffix;
+    second_end -= suffix;
+
+    if first_start < first_end && second_start < second_end {
+        let region = find_region(
+            &first_sequence[first_start..first_end],
+            &second_sequence[second_start..second_end],
+        );
+        match region {
+            Some(m) => {
+                let (i, j) = (m.first_start + first_start, m.second_start + second_start);
+                histogram(
+                    first_sequence,
+                    first_start,
+                    i,
+                    second_sequence,
+                    second_start,
+                    j,
+                    matches,
+                );
+                matches.push(Match {
+                    first_start: i,
+                    second_start: j,
+                    size: m.size,
+                });
+                histogram(
+                    first_sequence,
+                    i + m.size,
+                    first_end,
+                    second_sequence,
+                    j + m.size,
+                    second_end,
+                    matches,
+                );
+            }
+            None => {
+                let blocks = myers::matching_blocks(
+                    &first_sequence[first_start..first_end],
+                    &second_sequence[second_start..second_end],
+                );
+                matches.extend(blocks.into_iter().map(|m| Match {
+                    first_start: m.first_start + first_start,
+                    second_start: m.second_start + second_start,
+                    size: m.size,
+                }));
+            }
+        }
+    }
+
+    if suffix > 0 {
+        matches.push(Match {
+            first_start: first_end,
+            second_start: second_end,
+            size: suffix,
+        });
+    }
+}
+
+// Finds the common region whose rarest element is least frequent in the
+// first sequence, preferring longer regions on ties.
+fn find_region<T: Hash + Eq>(first_sequence: &[T], second_sequence: &[T]) -> Option<Match> {
+    let mut positions: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (i, item) in first_sequence.iter().enumerate() {
+        positions.entry(item).or_default().push(i);
+    }
+    let mut best: Option<Match> = None;
+    let mut best_count = MAX_CHAIN_LENGTH;
+    let mut j = 0;
+    while j < second_sequence.len() {
+        let mut next_j = j + 1;
+        let candidates = match positions.get(&second_sequence[j]) {
+            Some(candidates) if candidates.len() <= best_count => candidates,
+            _ => {
+                j = next_j;
+                continue;
+            }
+        };
+        for &i in candidates {
+            let (mut start_i, mut start_j) = (i, j);
+            while start_i > 0
+                && start_j > 0
+                && first_sequence[start_i - 1] == second_sequence[start_j - 1]
+            {
+                start_i -= 1;
+                start_j -= 1;
+            }
+            let (mut end_i, mut end_j) = (i + 1, j + 1);
+            while end_i < first_sequence.len()
+                && end_j < second_sequence.len()
+                && first_sequence[end_i] == second_sequence[end_j]
+            {
+                end_i += 1;
+                end_j += 1;
+            }
+            let count = first_sequence[start_i..end_i]
+                .iter()
+                .map(|item| positions[item].len())
+                .min()
+                .unwrap();
+            let size = end_i - start_i;
+            let better = match best {
+                Some(ref m) => count < best_count || (count == best_count && size > m.size),
+                None => count <= best_count,
+            };
+            if better {
+                best = Some(Match {
+                    first_start: start_i,
+                    second_start: start_j,
+                    size,
+                });
+                best_count = count;
+            }
+            // Positions inside this region would only find it again.
+            next_j = next_j.max(end_j);
+        }
+        j = next_j;
+    }
+    best
+}
@@ -0,0 +1,646 @@
+use differ::Differ;
+use std::cmp;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use is_character_junk;
+use utils::expand_tabs;
+
+const FILE_TEMPLATE: &str = "
+<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\"
+          \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">
+
+<html>
+
+<head>
+    <meta http-equiv=\"Content-Type\"
+          content=\"text/html; charset=utf-8\" />
+    <title></title>
+    <style type=\"text/css\">{styles}
+    </style>
+</head>
+
+<body>
+    {table}{legend}
+</body>
+
+</html>";
+
+const STYLES: &str = "
+        table.diff {font-family:Courier; border:medium;}
+        .diff_header {background-color:#e0e0e0}
+        td.diff_header {text-align:right}
+        .diff_next {background-color:#c0c0c0}
+        .diff_add {background-color:#aaffaa}
+        .diff_chg {background-color:#ffff77}
+        .diff_sub {background-color:#ffaaaa}";
+
+const TABLE_TEMPLATE: &str = "
+    <table class=\"diff\" id=\"difflib_chg_{prefix}_top\"
+           cellspacing=\"0\" cellpadding=\"0\" rules=\"groups\" >
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        {header_row}
+        <tbody>
+{data_rows}        </tbody>
+    </table>";
+
+const LEGEND: &str = "
+    <table class=\"diff\" summary=\"Legends\">
+        <tr> <th colspan=\"2\"> Legends </th> </tr>
+        <tr> <td> <table border=\"\" summary=\"Colors\">
+                      <tr><th> Colors </th> </tr>
+                      <tr><td class=\"diff_add\">&nbsp;Added&nbsp;</td></tr>
+                      <tr><td class=\"diff_chg\">Changed</td> </tr>
+                      <tr><td class=\"diff_sub\">Deleted</td> </tr>
+                  </table></td>
+             <td> <table border=\"\" summary=\"Links\">
+                      <tr><th colspan=\"2\"> Links </th> </tr>
+                      <tr><td>(f)irst change</td> </tr>
+                      <tr><td>(n)ext change</td> </tr>
+                      <tr><td>(t)op</td> </tr>
+                  </table></td> </tr>
+    </table>";
+
+// Anchor prefixes are shared by every HtmlDiff so that several tables can
+// live on the same page without their ids clashing.
+static DEFAULT_PREFIX: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+enum LineNumber {
+    Number(usize),
+    Blank,
+    Wrapped,
+}
+
+type Line = (LineNumber, String);
+type LinePair = (Option<Line>, Option<Line>, Option<bool>);
+
+pub struct HtmlDiff {
+    pub tab_size: usize,
+    pub wrap_column: Option<usize>,
+    pub differ: Differ,
+}
+
+impl Default for HtmlDiff {
+    fn default() -> HtmlDiff {
+        HtmlDiff::new()
+    }
+}
+
+impl HtmlDiff {
+    pub fn new() -> HtmlDiff {
+        HtmlDiff {
+            tab_size: 8,
+            wrap_column: None,
+            differ: Differ {
+                char_junk: Some(Box::new(is_character_junk)),
+                ..Differ::new()
+            },
+        }
+    }
+
+    pub fn make_file(
+        &self,
+        from_lines: &[&str],
+        to_lines: &[&str],
+        from_desc: &str,
+        to_desc: &str,
+        context: bool,
+        numlines: usize,
+    ) -> String {
+        let table = self.make_table(from_lines, to_lines, from_desc, to_desc, context, numlines);
+        fill_template(
+            FILE_TEMPLATE,
+            &[("styles", STYLES), ("table", &table), ("legend", LEGEND)],
+        )
+    }
+
+    pub fn make_table(
+        &self,
+        from_lines: &[&str],
+        to_lines: &[&str],
+        from_desc: &str,
+        to_desc: &str,
+        context: bool,
+        numlines: usize,
+    ) -> String {
+        let prefix = make_prefix();
+        let from_lines = self.tab_newline_replace(from_lines);
+        let to_lines = self.tab_newline_replace(to_lines);
+        let from_lines: Vec<&str> = from_lines.iter().map(|s| s.as_str()).collect();
+        let to_lines: Vec<&str> = to_lines.iter().map(|s| s.as_str()).collect();
+        let context_lines = if context { Some(numlines) } else { None };
+        let mut diffs = self.mdiff(&from_lines, &to_lines, context_lines);
+        if let Some(wrap_column) = self.wrap_column {
+            if wrap_column > 0 {
+                diffs = line_wrapper(diffs, wrap_column);
+            }
+        }
+        let (from_list, to_list, flag_list) = collect_lines(&diffs, &prefix);
+        let (from_list, to_list, flag_list, next_href, next_id) =
+            convert_flags(from_list, to_list, flag_list, context, numlines, &prefix[1]);
+
+        let mut data_rows = String::new();
+        for (i, flag) in flag_list.iter().enumerate() {
+            match *flag {
+                None => {
+                    if i > 0 {
+                        data_rows.push_str("        </tbody>        \n        <tbody>\n");
+                    }
+                }
+                Some(_) => {
+                    data_rows.push_str(&format!(
+                        "            <tr><td class=\"diff_next\"{}>{}</td>{}\
+                         <td class=\"diff_next\">{}</td>{}</tr>\n",
+                        next_id[i],
+                        next_href[i],
+                        from_list[i].as_ref().unwrap(),
+                        next_href[i],
+                        to_list[i].as_ref().unwrap()
+                    ));
+                }
+            }
+        }
+        let header_row = if !from_desc.is_empty() || !to_desc.is_empty() {
+            format!(
+                "<thead><tr><th class=\"diff_next\"><br /></th>\
+                 <th colspan=\"2\" class=\"diff_header\">{}</th>\
+                 <th class=\"diff_next\"><br /></th>\
+                 <th colspan=\"2\" class=\"diff_header\">{}</th></tr></thead>",
+                from_desc, to_desc
+            )
+        } else {
+            String::new()
+        };
+        fill_template(
+            TABLE_TEMPLATE,
+            &[
+                ("prefix", &prefix[1]),
+                ("header_row", &header_row),
+                ("data_rows", &data_rows),
+            ],
+        )
+        .replace("\0+", "<span class=\"diff_add\">")
+        .replace("\0-", "<span class=\"diff_sub\">")
+        .replace("\0^", "<span class=\"diff_chg\">")
+        .replace('\u{1}', "</span>")
+        .replace('\t', "&nbsp;")
+    }
+
+    fn tab_newline_replace(&self, lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                // Hide real spaces, expand tabs and turn the expansion back
+                // into tab characters which are replaced with markup at the end.
+                let line = line.replace(' ', "\0");
+                let line = expand_tabs(&line, self.tab_size).replace(' ', "\t");
+                line.replace('\0', " ").trim_end_matches('\n').to_string()
+            })
+            .collect()
+    }
+
+    fn mdiff(
+        &self,
+        from_lines: &[&str],
+        to_lines: &[&str],
+        context: Option<usize>,
+    ) -> Vec<LinePair> {
+        let diff_lines = self.differ.compare(from_lines, to_lines);
+        let pairs = line_pairs(line_iterator(diff_lines));
+        let context = match context {
+            Some(context) => context + 1,
+            None => return pairs,
+        };
+        let mut res = Vec::new();
+        let mut pairs = pairs.into_iter();
+        loop {
+            // Store lines up until we find a difference, only keeping around
+            // what we need for context.
+            let mut index = 0;
+            let mut context_lines: Vec<Option<LinePair>> = vec![None; context];
+            let mut found_diff = false;
+            while !found_diff {
+                let pair = match pairs.next() {
+                    Some(pair) => pair,
+                    None => return res,
+                };
+                found_diff = pair.2 == Some(true);
+                context_lines[index % context] = Some(pair);
+                index += 1;
+            }
+            let mut lines_to_write;
+            if index > context {
+                res.push((None, None, None));
+                lines_to_write = context;
+            } else {
+                lines_to_write = index;
+                index = 0;
+            }
+            while lines_to_write > 0 {
+                res.push(context_lines[index % context].take().unwrap());
+                index += 1;
+                lines_to_write -= 1;
+            }
+            // Now the context lines after the change.
+            lines_to_write = context - 1;
+            while lines_to_write > 0 {
+                let pair = match pairs.next() {
+                    Some(pair) => pair,
+                    None => return res,
+                };
+                if pair.2 == Some(true) {
+                    lines_to_write = context - 1;
+                } else {
+                    lines_to_write -= 1;
+                }
+                res.push(pair);
+            }
+        }
+    }
+}
+
+// Substitutes `{key}` placeholders in a single pass, so that user text which
+// happens to look like a placeholder is left alone.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut res = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = values.iter().find(|&&(key, _)| {
+            rest[1..].starts_with(key) && rest[1 + key.len()..].starts_with('}')
+        });
+        match value {
+            Some(&(key, value)) => {
+                res.push_str(value);
+                rest = &rest[key.len() + 2..];
+            }
+            None => {
+                res.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+fn make_prefix() -> [String; 2] {
+    let n = DEFAULT_PREFIX.fetch_add(1, Ordering::SeqCst);
+    [format!("from{}_", n), format!("to{}_", n)]
+}
+
+fn make_line(
+    lines: &mut VecDeque<String>,
+    format_key: Option<char>,
+    side: usize,
+    num_lines: &mut [usize; 2],
+    consume: bool,
+) -> Line {
+    num_lines[side] += 1;
+    let text = match format_key {
+        None => {
+            let line = if consume {
+                lines.pop_front().unwrap()
+            } else {
+                lines[0].clone()
+            };
+            line.chars().skip(2).collect()
+        }
+        Some('?') => {
+            let text: Vec<char> = lines.pop_front().unwrap().chars().collect();
+            let markers: Vec<char> = lines.pop_front().unwrap().chars().collect();
+            // Find runs of '+', '-' or '^' and wrap the matching text in
+            // marks that won't be noticed by the html escaping.
+            let mut text = text;
+            let mut spans = Vec::new();
+            let mut i = 0;
+            while i < markers.len() {
+                let c = markers[i];
+                if c == '+' || c == '-' || c == '^' {
+                    let begin = i;
+                    while i < markers.len() && markers[i] == c {
+                        i += 1;
+                    }
+                    spans.push((c, begin, i));
+                } else {
+                    i += 1;
+                }
+            }
+            for &(key, begin, end) in spans.iter().rev() {
+                let end = cmp::min(end, text.len());
+                let begin = cmp::min(begin, end);
+                let mut marked = Vec::with_capacity(text.len() + 3);
+                marked.extend_from_slice(&text[..begin]);
+                marked.push('\0');
+                marked.push(key);
+                marked.extend_from_slice(&text[begin..end]);
+                marked.push('\u{1}');
+                marked.extend_from_slice(&text[end..]);
+                text = marked;
+            }
+            text.into_iter().skip(2).collect()
+        }
+        Some(key) => {
+            let mut text: String = lines.pop_front().unwrap().chars().skip(2).collect();
+            // Give the user something to see on an empty added/deleted line.
+            if text.is_empty() {
+                text = String::from(" ");
+            }
+            format!("\0{}{}\u{1}", key, text)
+        }
+    };
+    (LineNumber::Number(num_lines[side]), text)
+}
+
+fn line_iterator(diff_lines: Vec<String>) -> Vec<LinePair> {
+    let mut res = Vec::new();
+    let mut diff_lines = diff_lines.into_iter();
+    let mut lines: VecDeque<String> = VecDeque::new();
+    let mut num_lines = [0, 0];
+    let (mut num_blanks_pending, mut num_blanks_to_yield): (isize, isize) = (0, 0);
+    let blank = || Some((LineNumber::Blank, String::from("\n")));
+    loop {
+        // Look ahead at the first character of the next four lines.
+        while lines.len() < 4 {
+            lines.push_back(diff_lines.next().unwrap_or_else(|| String::from("X")));
+        }
+        let s: String = lines
+            .iter()
+            .map(|l| l.chars().next().unwrap_or(' '))
+            .collect();
+        let (mut from_line, mut to_line) = (None, None);
+        if s.starts_with('X') {
+            num_blanks_to_yield = num_blanks_pending;
+        } else if s.starts_with("-?+?") {
+            let from = make_line(&mut lines, Some('?'), 0, &mut num_lines, true);
+            let to = make_line(&mut lines, Some('?'), 1, &mut num_lines, true);
+            res.push((Some(from), Some(to), Some(true)));
+            continue;
+        } else if s.starts_with("--++") {
+            num_blanks_pending -= 1;
+            let from = make_line(&mut lines, Some('-'), 0, &mut num_lines, true);
+            res.push((Some(from), None, Some(true)));
+            continue;
+        } else if s.starts_with("--?+") || s.starts_with("--+") || s.starts_with("- ") {
+            from_line = Some(make_line(&mut lines, Some('-'), 0, &mut num_lines, true));
+            num_blanks_to_yield = num_blanks_pending - 1;
+            num_blanks_pending = 0;
+        } else if s.starts_with("-+?") {
+            let from = make_line(&mut lines, None, 0, &mut num_lines, true);
+            let to = make_line(&mut lines, Some('?'), 1, &mut num_lines, true);
+            res.push((Some(from), Some(to), Some(true)));
+            continue;
+        } else if s.starts_with("-?+") {
+            let from = make_line(&mut lines, Some('?'), 0, &mut num_lines, true);
+            let to = make_line(&mut lines, None, 1, &mut num_lines, true);
+            res.push((Some(from), Some(to), Some(true)));
+            continue;
+        } else if s.starts_with('-') {
+            num_blanks_pending -= 1;
+            let from = make_line(&mut lines, Some('-'), 0, &mut num_lines, true);
+            res.push((Some(from), None, Some(true)));
+            continue;
+        } else if s.starts_with("+--") {
+            num_blanks_pending += 1;
+            let to = make_line(&mut lines, Some('+'), 1, &mut num_lines, true);
+            res.push((None, Some(to), Some(true)));
+            continue;
+        } else if s.starts_with("+ ") || s.starts_with("+-") {
+            to_line = Some(make_line(&mut lines, Some('+'), 1, &mut num_lines, true));
+            num_blanks_to_yield = num_blanks_pending + 1;
+            num_blanks_pending = 0;
+        } else if s.starts_with('+') {
+            num_blanks_pending += 1;
+            let to = make_line(&mut lines, Some('+'), 1, &mut num_lines, true);
+            res.push((None, Some(to), Some(true)));
+            continue;
+        } else if s.starts_with(' ') {
+            let from = make_line(&mut lines, None, 0, &mut num_lines, false);
+            let to = make_line(&mut lines, None, 1, &mut num_lines, true);
+            res.push((Some(from), Some(to), Some(false)));
+            continue;
+        }
+        // Catch up on the blank lines so the next from/to pair lines up.
+        while num_blanks_to_yield < 0 {
+            num_blanks_to_yield += 1;
+            res.push((None, blank(), Some(true)));
+        }
+        while num_blanks_to_yield > 0 {
+            num_blanks_to_yield -= 1;
+            res.push((blank(), None, Some(true)));
+        }
+        if s.starts_with('X') {
+            return res;
+        }
+        res.push((from_line, to_line, Some(true)));
+    }
+}
+
+fn line_pairs(lines: Vec<LinePair>) -> Vec<LinePair> {
+    let mut res = Vec::new();
+    let mut from_lines = VecDeque::new();
+    let mut to_lines = VecDeque::new();
+    let mut lines = lines.into_iter();
+    loop {
+        while from_lines.is_empty() || to_lines.is_empty() {
+            let (from_line, to_line, found_diff) = match lines.next() {
+                Some(line) => line,
+                None => return res,
+            };
+            if let Some(from_line) = from_line {
+                from_lines.push_back((from_line, found_diff));
+            }
+            if let Some(to_line) = to_line {
+                to_lines.push_back((to_line, found_diff));
+            }
+        }
+        let (from_line, from_diff) = from_lines.pop_front().unwrap();
+        let (to_line, to_diff) = to_lines.pop_front().unwrap();
+        let found_diff = from_diff == Some(true) || to_diff == Some(true);
+        res.push((Some(from_line), Some(to_line), Some(found_diff)));
+    }
+}
+
+fn split_line(data_list: &mut Vec<Line>, line_num: LineNumber, text: Vec<char>, max: usize) {
+    // Blank lines and context separators are never wrapped.
+    if line_num == LineNumber::Blank {
+        data_list.push((line_num, text.into_iter().collect()));
+        return;
+    }
+    let size = text.len();
+    let marks = text.iter().filter(|&&c| c == '\0').count();
+    if size <= max || size - marks * 3 <= max {
+        data_list.push((line_num, text.into_iter().collect()));
+        return;
+    }
+    // Scan for the wrap point, keeping track of whether it is inside markers.
+    let (mut i, mut n) = (0, 0);
+    let mut mark = None;
+    while n < max && i < size {
+        if text[i] == '\0' {
+            i += 1;
+            mark = text.get(i).cloned();
+            i += 1;
+        } else if text[i] == '\u{1}' {
+            i += 1;
+            mark = None;
+        } else {
+            i += 1;
+            n += 1;
+        }
+    }
+    let i = cmp::min(i, size);
+    let mut first: String = text[..i].iter().collect();
+    let mut second = Vec::with_capacity(size - i + 2);
+    // Each wrapped part gets its own markup, so close and reopen the marker.
+    if let Some(mark) = mark {
+        first.push('\u{1}');
+        second.push('\0');
+        second.push(mark);
+    }
+    second.extend_from_slice(&text[i..]);
+    data_list.push((line_num, first));
+    split_line(data_list, LineNumber::Wrapped, second, max);
+}
+
+fn line_wrapper(diffs: Vec<LinePair>, wrap_column: usize) -> Vec<LinePair> {
+    let mut res = Vec::new();
+    for (from_data, to_data, flag) in diffs {
+        let (from_data, to_data) = match (from_data, to_data) {
+            (Some(from_data), Some(to_data)) if flag.is_some() => (from_data, to_data),
+            (from_data, to_data) => {
+                res.push((from_data, to_data, flag));
+                continue;
+            }
+        };
+        let mut from_list = Vec::new();
+        let mut to_list = Vec::new();
+        split_line(
+            &mut from_list,
+            from_data.0,
+            from_data.1.chars().collect(),
+            wrap_column,
+        );
+        split_line(
+            &mut to_list,
+            to_data.0,
+            to_data.1.chars().collect(),
+            wrap_column,
+        );
+        let mut from_list = from_list.into_iter();
+        let mut to_list = to_list.into_iter();
+        loop {
+            let (from_line, to_line) = match (from_list.next(), to_list.next()) {
+                (None, None) => break,
+                (from_line, to_line) => (from_line, to_line),
+            };
+            let filler = || (LineNumber::Blank, String::from(" "));
+            res.push((
+                Some(from_line.unwrap_or_else(filler)),
+                Some(to_line.unwrap_or_else(filler)),
+                flag,
+            ));
+        }
+    }
+    res
+}
+
+fn format_line(prefix: &str, line: &Line) -> String {
+    let (ref line_num, ref text) = *line;
+    let (id, line_num) = match *line_num {
+        LineNumber::Number(n) => (format!(" id=\"{}{}\"", prefix, n), n.to_string()),
+        LineNumber::Blank => (String::new(), String::new()),
+        LineNumber::Wrapped => (String::new(), String::from(">")),
+    };
+    let text = text
+        .replace('&', "&amp;")
+        .replace('>', "&gt;")
+        .replace('<', "&lt;")
+        .replace(' ', "&nbsp;");
+    format!(
+        "<td class=\"diff_header\"{}>{}</td><td nowrap=\"nowrap\">{}</td>",
+        id,
+        line_num,
+        text.trim_end()
+    )
+}
+
+type CollectedLines = (Vec<Option<String>>, Vec<Option<String>>, Vec<Option<bool>>);
+
+fn collect_lines(diffs: &[LinePair], prefix: &[String; 2]) -> CollectedLines {
+    let (mut from_list, mut to_list, mut flag_list) = (Vec::new(), Vec::new(), Vec::new());
+    for &(ref from_data, ref to_data, flag) in diffs {
+        match (from_data, to_data) {
+            (Some(from_data), Some(to_data)) => {
+                from_list.push(Some(format_line(&prefix[0], from_data)));
+                to_list.push(Some(format_line(&prefix[1], to_data)));
+            }
+            _ => {
+                from_list.push(None);
+                to_list.push(None);
+            }
+        }
+        flag_list.push(flag);
+    }
+    (from_list, to_list, flag_list)
+}
+
+type ConvertedFlags = (
+    Vec<Option<String>>,
+    Vec<Option<String>>,
+    Vec<Option<bool>>,
+    Vec<String>,
+    Vec<String>,
+);
+
+fn convert_flags(
+    from_list: Vec<Option<String>>,
+    to_list: Vec<Option<String>>,
+    flag_list: Vec<Option<bool>>,
+    context: bool,
+    numlines: usize,
+    to_prefix: &str,
+) -> ConvertedFlags {
+    let (mut from_list, mut to_list, mut flag_list) = (from_list, to_list, flag_list);
+    let mut next_id = vec![String::new(); flag_list.len()];
+    let mut next_href = vec![String::new(); flag_list.len()];
+    let (mut num_chg, mut in_change) = (0, false);
+    let mut last = 0;
+    for (i, flag) in flag_list.iter().enumerate() {
+        if *flag == Some(true) {
+            if !in_change {
+                in_change = true;
+                last = i;
+                // Drop an anchor a few lines before the change for the
+                // previous link, and a link to the next change.
+                next_id[i.saturating_sub(numlines)] =
+                    format!(" id=\"difflib_chg_{}_{}\"", to_prefix, num_chg);
+                num_chg += 1;
+                next_href[last] =
+                    format!("<a href=\"#difflib_chg_{}_{}\">n</a>", to_prefix, num_chg);
+            }
+        } else {
+            in_change = false;
+        }
+    }
+    if flag_list.is_empty() {
+        flag_list = vec![Some(false)];
+        next_id = vec![String::new()];
+        next_href = vec![String::new()];
+        last = 0;
+        let message = if context {
+            "<td></td><td>&nbsp;No Differences Found&nbsp;</td>"
+        } else {
+            "<td></td><td>&nbsp;Empty File&nbsp;</td>"
+        };
+        from_list = vec![Some(message.to_string())];
+        to_list = from_list.clone();
+    }
+    if flag_list[0] != Some(true) {
+        next_href[0] = format!("<a href=\"#difflib_chg_{}_0\">f</a>", to_prefix);
+    }
+    next_href[last] = format!("<a href=\"#difflib_chg_{}_top\">t</a>", to_prefix);
+    (from_list, to_list, flag_list, next_href, next_id)
+}
@@ -1,11 +1,117 @@
+#[cfg(feature = "regex")]
+extern crate regex;
+extern crate unicode_segmentation;
+
+pub mod color;
+pub mod compare;
 pub mod differ;
+pub mod git;
+mod histogram;
+pub mod html;
+pub mod merge;
+mod myers;
+mod patience;
+pub mod patch;
 pub mod sequencematcher;
+pub mod sidebyside;
 mod utils;
+pub mod worddiff;
+
+use compare::CompareOptions;
+use differ::{CharJunk, Differ, LineJunk};
+use sequencematcher::{Algorithm, Opcode, Sequence, SequenceMatcher, Tag};
+use std::fmt::{self, Display};
+use std::io;
+use utils::{format_range_context, format_range_normal, format_range_unified};
 
-use sequencematcher::{Sequence, SequenceMatcher};
-use std::collections::HashMap;
-use std::fmt::Display;
-use utils::{format_range_context, format_range_unified};
+/// Settings shared by `unified_diff_with_options` and `context_diff_with_options`.
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Number of context lines around each change.
+    pub n: usize,
+    pub algorithm: Algorithm,
+    /// Terminator of the header lines. With an empty `lineterm` the compared
+    /// items are taken to be lines without terminators as well, otherwise an
+    /// unterminated last item is followed by a "\ No newline at end of file"
+    /// marker.
+    pub lineterm: String,
+    /// Differences in white space, case and blank lines to overlook.
+    pub compare: CompareOptions,
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions::new()
+    }
+}
+
+impl DiffOptions {
+    pub fn new() -> DiffOptions {
+        DiffOptions {
+            n: 3,
+            algorithm: Algorithm::default(),
+            lineterm: "\n".to_string(),
+            compare: CompareOptions::new(),
+        }
+    }
+
+    fn matcher<'a, U: Sequence>(
+        &self,
+        first_sequence: &'a [U],
+        second_sequence: &'a [U],
+    ) -> SequenceMatcher<'a, U> {
+        let mut matcher = SequenceMatcher::new(first_sequence, second_sequence);
+        matcher.set_algorithm(self.algorithm);
+        matcher
+    }
+
+    fn grouped_opcodes<T: Sequence + Display>(
+        &self,
+        first_sequence: &[T],
+        second_sequence: &[T],
+    ) -> Vec<Vec<Opcode>> {
+        let mut groups = if self.compare.normalizes() {
+            let first_keys = self.compare.normalize_all(first_sequence);
+            let second_keys = self.compare.normalize_all(second_sequence);
+            let mut matcher = self.matcher(&first_keys, &second_keys);
+            matcher.get_grouped_opcodes(self.n)
+        } else {
+            self.matcher(first_sequence, second_sequence)
+                .get_grouped_opcodes(self.n)
+        };
+        groups.retain(|group| {
+            group.iter().any(|opcode| {
+                opcode.tag != Tag::Equal
+                    && !self
+                        .compare
+                        .is_ignored(opcode, first_sequence, second_sequence)
+            })
+        });
+        groups
+    }
+
+    fn opcodes<T: Sequence + Display>(
+        &self,
+        first_sequence: &[T],
+        second_sequence: &[T],
+    ) -> Vec<Opcode> {
+        let mut opcodes = if self.compare.normalizes() {
+            let first_keys = self.compare.normalize_all(first_sequence);
+            let second_keys = self.compare.normalize_all(second_sequence);
+            let mut matcher = self.matcher(&first_keys, &second_keys);
+            matcher.get_opcodes()
+        } else {
+            self.matcher(first_sequence, second_sequence).get_opcodes()
+        };
+        opcodes.retain(|opcode| {
+            opcode.tag == Tag::Equal
+                || !self
+                    .compare
+                    .is_ignored(opcode, first_sequence, second_sequence)
+        });
+        opcodes
+    }
+}
 
 pub fn get_close_matches<'a>(
     word: &str,
@@ -20,9 +126,11 @@ pub fn get_close_matches<'a>(
     let mut matcher = SequenceMatcher::new("", word);
     for i in &possibilities {
         matcher.set_first_seq(i);
-        let ratio = matcher.ratio();
-        if ratio >= cutoff {
-            res.push((ratio, i));
+        if matcher.real_quick_ratio() >= cutoff
+            && matcher.quick_ratio() >= cutoff
+            && matcher.ratio() >= cutoff
+        {
+            res.push((matcher.ratio(), i));
         }
     }
     res.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
@@ -30,6 +138,29 @@ pub fn get_close_matches<'a>(
     res.iter().map(|x| x.1).collect()
 }
 
+pub fn is_line_junk(line: &&str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line == "#"
+}
+
+pub fn is_character_junk(ch: &char) -> bool {
+    *ch == ' ' || *ch == '\t'
+}
+
+pub fn ndiff(
+    first_sequence: &[&str],
+    second_sequence: &[&str],
+    line_junk: Option<LineJunk>,
+    char_junk: Option<CharJunk>,
+) -> Vec<String> {
+    let differ = Differ {
+        line_junk,
+        char_junk,
+        ..Differ::new()
+    };
+    differ.compare(first_sequence, second_sequence)
+}
+
 pub fn unified_diff<T: Sequence + Display>(
     first_sequence: &[T],
     second_sequence: &[T],
@@ -39,13 +170,55 @@ pub fn unified_diff<T: Sequence + Display>(
     to_file_date: &str,
     n: usize,
 ) -> Vec<String> {
-    let mut res = Vec::new();
-    let lineterm = '\n';
-    let mut started = false;
-    let mut matcher = SequenceMatcher::new(first_sequence, second_sequence);
-    for group in &matcher.get_grouped_opcodes(n) {
-        if !started {
-            started = true;
+    unified_diff_iter(
+        first_sequence,
+        second_sequence,
+        from_file,
+        to_file,
+        from_file_date,
+        to_file_date,
+        n,
+    ).collect()
+}
+
+pub fn unified_diff_iter<'a, T: Sequence + Display>(
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    from_file: &'a str,
+    to_file: &'a str,
+    from_file_date: &'a str,
+    to_file_date: &'a str,
+    n: usize,
+) -> impl Iterator<Item = String> + 'a {
+    let options = DiffOptions {
+        n,
+        ..DiffOptions::default()
+    };
+    unified_diff_with_options(
+        first_sequence,
+        second_sequence,
+        from_file,
+        to_file,
+        from_file_date,
+        to_file_date,
+        &options,
+    )
+}
+
+pub fn unified_diff_with_options<'a, T: Sequence + Display>(
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    from_file: &'a str,
+    to_file: &'a str,
+    from_file_date: &'a str,
+    to_file_date: &'a str,
+    options: &DiffOptions,
+) -> impl Iterator<Item = String> + 'a {
+    let lineterm = options.lineterm.clone();
+    let groups = options.grouped_opcodes(first_sequence, second_sequence);
+    groups.into_iter().enumerate().flat_map(move |(i, group)| {
+        let mut res = Vec::new();
+        if i == 0 {
             let from_date = format!("\t{}", from_file_date);
             let to_date = format!("\t{}", to_file_date);
             res.push(format!("--- {}{}{}", from_file, from_date, lineterm));
@@ -58,38 +231,41 @@ pub fn unified_diff<T: Sequence + Display>(
             "@@ -{} +{} @@{}",
             file1_range, file2_range, lineterm
         ));
-        for code in group {
-            if code.tag == "equal" {
-                for item in first_sequence
-                    .iter()
-                    .take(code.first_end)
-                    .skip(code.first_start)
-                {
-                    res.push(format!(" {}", item));
-                }
+        for code in &group {
+            if code.tag == Tag::Equal {
+                push_lines(
+                    &mut res,
+                    " ",
+                    first_sequence,
+                    code.first_start,
+                    code.first_end,
+                    &lineterm,
+                );
                 continue;
             }
-            if code.tag == "replace" || code.tag == "delete" {
-                for item in first_sequence
-                    .iter()
-                    .take(code.first_end)
-                    .skip(code.first_start)
-                {
-                    res.push(format!("-{}", item));
-                }
+            if code.tag == Tag::Replace || code.tag == Tag::Delete {
+                push_lines(
+                    &mut res,
+                    "-",
+                    first_sequence,
+                    code.first_start,
+                    code.first_end,
+                    &lineterm,
+                );
             }
-            if code.tag == "replace" || code.tag == "insert" {
-                for item in second_sequence
-                    .iter()
-                    .take(code.second_end)
-                    .skip(code.second_start)
-                {
-                    res.push(format!("+{}", item));
-                }
+            if code.tag == Tag::Replace || code.tag == Tag::Insert {
+                push_lines(
+                    &mut res,
+                    "+",
+                    second_sequence,
+                    code.second_start,
+                    code.second_end,
+                    &lineterm,
+                );
             }
         }
-    }
-    res
+        res
+    })
 }
 
 pub fn context_diff<T: Sequence + Display>(
@@ -101,18 +277,55 @@ pub fn context_diff<T: Sequence + Display>(
     to_file_date: &str,
     n: usize,
 ) -> Vec<String> {
-    let mut res = Vec::new();
-    let lineterm = '\n';
-    let mut prefix: HashMap<String, String> = HashMap::new();
-    prefix.insert(String::from("insert"), String::from("+ "));
-    prefix.insert(String::from("delete"), String::from("- "));
-    prefix.insert(String::from("replace"), String::from("! "));
-    prefix.insert(String::from("equal"), String::from("  "));
-    let mut started = false;
-    let mut matcher = SequenceMatcher::new(first_sequence, second_sequence);
-    for group in &matcher.get_grouped_opcodes(n) {
-        if !started {
-            started = true;
+    context_diff_iter(
+        first_sequence,
+        second_sequence,
+        from_file,
+        to_file,
+        from_file_date,
+        to_file_date,
+        n,
+    ).collect()
+}
+
+pub fn context_diff_iter<'a, T: Sequence + Display>(
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    from_file: &'a str,
+    to_file: &'a str,
+    from_file_date: &'a str,
+    to_file_date: &'a str,
+    n: usize,
+) -> impl Iterator<Item = String> + 'a {
+    let options = DiffOptions {
+        n,
+        ..DiffOptions::default()
+    };
+    context_diff_with_options(
+        first_sequence,
+        second_sequence,
+        from_file,
+        to_file,
+        from_file_date,
+        to_file_date,
+        &options,
+    )
+}
+
+pub fn context_diff_with_options<'a, T: Sequence + Display>(
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    from_file: &'a str,
+    to_file: &'a str,
+    from_file_date: &'a str,
+    to_file_date: &'a str,
+    options: &DiffOptions,
+) -> impl Iterator<Item = String> + 'a {
+    let lineterm = options.lineterm.clone();
+    let groups = options.grouped_opcodes(first_sequence, second_sequence);
+    groups.into_iter().enumerate().flat_map(move |(i, group)| {
+        let mut res = Vec::new();
+        if i == 0 {
             let from_date = format!("\t{}", from_file_date);
             let to_date = format!("\t{}", to_file_date);
             res.push(format!("*** {}{}{}", from_file, from_date, lineterm));
@@ -123,47 +336,229 @@ pub fn context_diff<T: Sequence + Display>(
         let file1_range = format_range_context(first.first_start, last.first_end);
         res.push(format!("*** {} ****{}", file1_range, lineterm));
         let mut any = false;
-        for opcode in group {
-            if opcode.tag == "replace" || opcode.tag == "delete" {
+        for opcode in &group {
+            if opcode.tag == Tag::Replace || opcode.tag == Tag::Delete {
                 any = true;
                 break;
             }
         }
         if any {
-            for opcode in group {
-                if opcode.tag != "insert" {
-                    for item in first_sequence
-                        .iter()
-                        .take(opcode.first_end)
-                        .skip(opcode.first_start)
-                    {
-                        res.push(format!("{}{}", &prefix[&opcode.tag], item));
-                    }
+            for opcode in &group {
+                if opcode.tag != Tag::Insert {
+                    push_lines(
+                        &mut res,
+                        context_prefix(opcode.tag),
+                        first_sequence,
+                        opcode.first_start,
+                        opcode.first_end,
+                        &lineterm,
+                    );
                 }
             }
         }
         let file2_range = format_range_context(first.second_start, last.second_end);
         res.push(format!("--- {} ----{}", file2_range, lineterm));
         any = false;
-        for opcode in group {
-            if opcode.tag == "replace" || opcode.tag == "insert" {
+        for opcode in &group {
+            if opcode.tag == Tag::Replace || opcode.tag == Tag::Insert {
                 any = true;
                 break;
             }
         }
         if any {
-            for opcode in group {
-                if opcode.tag != "delete" {
-                    for item in second_sequence
-                        .iter()
-                        .take(opcode.second_end)
-                        .skip(opcode.second_start)
-                    {
-                        res.push(format!("{}{}", prefix[&opcode.tag], item));
+            for opcode in &group {
+                if opcode.tag != Tag::Delete {
+                    push_lines(
+                        &mut res,
+                        context_prefix(opcode.tag),
+                        second_sequence,
+                        opcode.second_start,
+                        opcode.second_end,
+                        &lineterm,
+                    );
+                }
+            }
+        }
+        res
+    })
+}
+
+/// Classic `diff` output with `3c3`, `5a6,7` and `5,6d4` commands.
+pub fn normal_diff<T: Sequence + Display>(
+    first_sequence: &[T],
+    second_sequence: &[T],
+) -> Vec<String> {
+    normal_diff_with_options(first_sequence, second_sequence, &DiffOptions::default()).collect()
+}
+
+/// Like `normal_diff`, `options.n` is ignored since there is no context.
+pub fn normal_diff_with_options<'a, T: Sequence + Display>(
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    options: &DiffOptions,
+) -> impl Iterator<Item = String> + 'a {
+    let lineterm = options.lineterm.clone();
+    let opcodes = options.opcodes(first_sequence, second_sequence);
+    opcodes
+        .into_iter()
+        .filter(|opcode| opcode.tag != Tag::Equal)
+        .flat_map(move |opcode| {
+            let mut res = Vec::new();
+            let (command, first_range, second_range) = match opcode.tag {
+                Tag::Insert => (
+                    'a',
+                    opcode.first_start.to_string(),
+                    format_range_normal(opcode.second_start, opcode.second_end),
+                ),
+                Tag::Delete => (
+                    'd',
+                    format_range_normal(opcode.first_start, opcode.first_end),
+                    opcode.second_start.to_string(),
+                ),
+                _ => (
+                    'c',
+                    format_range_normal(opcode.first_start, opcode.first_end),
+                    format_range_normal(opcode.second_start, opcode.second_end),
+                ),
+            };
+            res.push(format!(
+                "{}{}{}{}",
+                first_range, command, second_range, lineterm
+            ));
+            push_lines(
+                &mut res,
+                "< ",
+                first_sequence,
+                opcode.first_start,
+                opcode.first_end,
+                &lineterm,
+            );
+            if opcode.tag == Tag::Replace {
+                res.push(format!("---{}", lineterm));
+            }
+            push_lines(
+                &mut res,
+                "> ",
+                second_sequence,
+                opcode.second_start,
+                opcode.second_end,
+                &lineterm,
+            );
+            res
+        })
+}
+
+/// Script of `ed` commands turning the first sequence into the second, like
+/// `diff -e`. Commands are in reverse order so that line numbers stay valid.
+pub fn ed_diff<T: Sequence + Display>(first_sequence: &[T], second_sequence: &[T]) -> Vec<String> {
+    ed_diff_with_options(first_sequence, second_sequence, &DiffOptions::default()).collect()
+}
+
+pub fn ed_diff_with_options<'a, T: Sequence + Display>(
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    options: &DiffOptions,
+) -> impl Iterator<Item = String> + 'a {
+    let lineterm = options.lineterm.clone();
+    let opcodes = options.opcodes(first_sequence, second_sequence);
+    opcodes
+        .into_iter()
+        .rev()
+        .filter(|opcode| opcode.tag != Tag::Equal)
+        .flat_map(move |opcode| {
+            let mut res = Vec::new();
+            let command = match opcode.tag {
+                Tag::Insert => format!("{}a", opcode.first_start),
+                Tag::Delete => format!(
+                    "{}d",
+                    format_range_normal(opcode.first_start, opcode.first_end)
+                ),
+                _ => format!(
+                    "{}c",
+                    format_range_normal(opcode.first_start, opcode.first_end)
+                ),
+            };
+            res.push(format!("{}{}", command, lineterm));
+            if opcode.tag == Tag::Delete {
+                return res;
+            }
+            let lines = &second_sequence[opcode.second_start..opcode.second_end];
+            for (i, item) in lines.iter().enumerate() {
+                let mut line = item.to_string();
+                if !lineterm.is_empty() && !line.ends_with('\n') {
+                    line.push_str(&lineterm);
+                }
+                // A lone "." would end the input, write ".." and strip the
+                // extra dot once input mode is left.
+                if line.trim_end_matches(&['\n', '\r'][..]) == "." {
+                    res.push(format!("..{}", lineterm));
+                    res.push(format!(".{}", lineterm));
+                    res.push(format!("s/.//{}", lineterm));
+                    if i + 1 < lines.len() {
+                        res.push(format!("a{}", lineterm));
+                    }
+                } else {
+                    res.push(line);
+                    if i + 1 == lines.len() {
+                        res.push(format!(".{}", lineterm));
                     }
                 }
             }
+            res
+        })
+}
+
+pub fn write_diff<W, I>(writer: &mut W, lines: I) -> io::Result<()>
+where
+    W: io::Write,
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    for line in lines {
+        writer.write_all(line.as_ref().as_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn write_diff_fmt<W, I>(writer: &mut W, lines: I) -> fmt::Result
+where
+    W: fmt::Write,
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    for line in lines {
+        writer.write_str(line.as_ref())?;
+    }
+    Ok(())
+}
+
+// Pushes `sequence[start..end]` with the given prefix. An unterminated last
+// item of the sequence gets the "\ No newline at end of file" marker, unless
+// `lineterm` is empty.
+fn push_lines<T: Display>(
+    res: &mut Vec<String>,
+    prefix: &str,
+    sequence: &[T],
+    start: usize,
+    end: usize,
+    lineterm: &str,
+) {
+    for (index, item) in sequence.iter().enumerate().take(end).skip(start) {
+        let line = format!("{}{}", prefix, item);
+        if index + 1 == sequence.len() && !lineterm.is_empty() && !line.ends_with('\n') {
+            res.push(format!("{}{}", line, lineterm));
+            res.push(format!("\\ No newline at end of file{}", lineterm));
+        } else {
+            res.push(line);
         }
     }
-    res
+}
+
+fn context_prefix(tag: Tag) -> &'static str {
+    match tag {
+        Tag::Insert => "+ ",
+        Tag::Delete => "- ",
+        Tag::Replace => "! ",
+        Tag::Equal => "  ",
+    }
 }
@@ -0,0 +1,242 @@
+use sequencematcher::{Algorithm, Match, SequenceMatcher};
+use std::cmp;
+use utils::{common_prefix_len, common_suffix_len};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ConflictStyle {
+    /// Only our and their side are shown, lines both sides agree on at the
+    /// start and end of a conflict are moved out of it.
+    #[default]
+    Merge,
+    /// The base is shown between `|||||||` and `=======`.
+    Diff3,
+    /// Like `Diff3`, with common lines moved out of the conflict as in `Merge`.
+    ZealousDiff3,
+}
+
+/// Settings for `merge_with_options`.
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    pub style: ConflictStyle,
+    pub algorithm: Algorithm,
+    /// Text after the `<<<<<<<` marker, nothing is written when empty.
+    pub ours_label: String,
+    /// Text after the `|||||||` marker.
+    pub base_label: String,
+    /// Text after the `>>>>>>>` marker.
+    pub theirs_label: String,
+}
+
+impl Default for MergeOptions {
+    fn default() -> MergeOptions {
+        MergeOptions::new()
+    }
+}
+
+impl MergeOptions {
+    pub fn new() -> MergeOptions {
+        MergeOptions {
+            style: ConflictStyle::default(),
+            algorithm: Algorithm::default(),
+            ours_label: "ours".to_string(),
+            base_label: "base".to_string(),
+            theirs_label: "theirs".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Index of the `<<<<<<<` marker in `MergeResult::lines`.
+    pub line: usize,
+    /// Position of each side in the sequence it comes from.
+    pub base_start: usize,
+    pub ours_start: usize,
+    pub theirs_start: usize,
+    pub base: Vec<String>,
+    pub ours: Vec<String>,
+    pub theirs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    /// Merged lines, with conflict markers around each conflict.
+    pub lines: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+// Stretch of lines matched in all three sequences.
+struct SyncRegion {
+    base_start: usize,
+    base_end: usize,
+    ours_start: usize,
+    ours_end: usize,
+    theirs_start: usize,
+    theirs_end: usize,
+}
+
+pub fn merge(base: &[&str], ours: &[&str], theirs: &[&str]) -> MergeResult {
+    merge_with_options(base, ours, theirs, &MergeOptions::new())
+}
+
+/// Three-way merge of `ours` and `theirs`, both derived from `base`. Changes
+/// made on only one side are taken, overlapping different changes become
+/// conflicts surrounded by git-style markers.
+pub fn merge_with_options(
+    base: &[&str],
+    ours: &[&str],
+    theirs: &[&str],
+    options: &MergeOptions,
+) -> MergeResult {
+    let mut res = MergeResult {
+        lines: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    let (mut base_index, mut ours_index, mut theirs_index) = (0, 0, 0);
+    for region in sync_regions(base, ours, theirs, options.algorithm) {
+        let base_part = &base[base_index..region.base_start];
+        let ours_part = &ours[ours_index..region.ours_start];
+        let theirs_part = &theirs[theirs_index..region.theirs_start];
+        if ours_part == theirs_part || theirs_part == base_part {
+            push_lines(&mut res.lines, ours_part);
+        } else if ours_part == base_part {
+            push_lines(&mut res.lines, theirs_part);
+        } else {
+            push_conflict(
+                &mut res,
+                options,
+                (base_index, base_part),
+                (ours_index, ours_part),
+                (theirs_index, theirs_part),
+            );
+        }
+        push_lines(&mut res.lines, &base[region.base_start..region.base_end]);
+        base_index = region.base_end;
+        ours_index = region.ours_end;
+        theirs_index = region.theirs_end;
+    }
+    res
+}
+
+fn matching_blocks(first: &[&str], second: &[&str], algorithm: Algorithm) -> Vec<Match> {
+    let mut matcher = SequenceMatcher::with_autojunk(first, second, false);
+    matcher.set_algorithm(algorithm);
+    matcher.get_matching_blocks()
+}
+
+// Intersects the base ranges matched by ours and by theirs. The last region
+// is an empty one at the end of all three sequences.
+fn sync_regions(
+    base: &[&str],
+    ours: &[&str],
+    theirs: &[&str],
+    algorithm: Algorithm,
+) -> Vec<SyncRegion> {
+    let ours_matches = matching_blocks(base, ours, algorithm);
+    let theirs_matches = matching_blocks(base, theirs, algorithm);
+    let mut regions = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < ours_matches.len() && j < theirs_matches.len() {
+        let (ours_match, theirs_match) = (&ours_matches[i], &theirs_matches[j]);
+        let ours_end = ours_match.first_start + ours_match.size;
+        let theirs_end = theirs_match.first_start + theirs_match.size;
+        let start = cmp::max(ours_match.first_start, theirs_match.first_start);
+        let end = cmp::min(ours_end, theirs_end);
+        if start < end {
+            let ours_start = ours_match.second_start + start - ours_match.first_start;
+            let theirs_start = theirs_match.second_start + start - theirs_match.first_start;
+            regions.push(SyncRegion {
+                base_start: start,
+                base_end: end,
+                ours_start,
+                ours_end: ours_start + end - start,
+                theirs_start,
+                theirs_end: theirs_start + end - start,
+            });
+        }
+        if ours_end < theirs_end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    regions.push(SyncRegion {
+        base_start: base.len(),
+        base_end: base.len(),
+        ours_start: ours.len(),
+        ours_end: ours.len(),
+        theirs_start: theirs.len(),
+        theirs_end: theirs.len(),
+    });
+    regions
+}
+
+fn push_lines(lines: &mut Vec<String>, part: &[&str]) {
+    lines.extend(part.iter().map(|line| line.to_string()));
+}
+
+// Pushes a conflict side followed by a marker, making sure the marker starts
+// on a line of its own.
+fn push_side(lines: &mut Vec<String>, part: &[&str]) {
+    push_lines(lines, part);
+    if let Some(last) = lines.last_mut() {
+        if !last.ends_with('\n') {
+            last.push('\n');
+        }
+    }
+}
+
+fn marker(marker: &str, label: &str) -> String {
+    if label.is_empty() {
+        format!("{}\n", marker)
+    } else {
+        format!("{} {}\n", marker, label)
+    }
+}
+
+fn push_conflict(
+    res: &mut MergeResult,
+    options: &MergeOptions,
+    (base_start, base): (usize, &[&str]),
+    (mut ours_start, mut ours): (usize, &[&str]),
+    (mut theirs_start, mut theirs): (usize, &[&str]),
+) {
+    let mut suffix: &[&str] = &[];
+    if options.style != ConflictStyle::Diff3 {
+        let prefix = common_prefix_len(ours, theirs);
+        push_lines(&mut res.lines, &ours[..prefix]);
+        ours_start += prefix;
+        theirs_start += prefix;
+        ours = &ours[prefix..];
+        theirs = &theirs[prefix..];
+        let suffix_len = common_suffix_len(ours, theirs);
+        suffix = &ours[ours.len() - suffix_len..];
+        ours = &ours[..ours.len() - suffix_len];
+        theirs = &theirs[..theirs.len() - suffix_len];
+    }
+    res.conflicts.push(Conflict {
+        line: res.lines.len(),
+        base_start,
+        ours_start,
+        theirs_start,
+        base: base.iter().map(|line| line.to_string()).collect(),
+        ours: ours.iter().map(|line| line.to_string()).collect(),
+        theirs: theirs.iter().map(|line| line.to_string()).collect(),
+    });
+    res.lines.push(marker("<<<<<<<", &options.ours_label));
+    push_side(&mut res.lines, ours);
+    if options.style != ConflictStyle::Merge {
+        res.lines.push(marker("|||||||", &options.base_label));
+        push_side(&mut res.lines, base);
+    }
+    res.lines.push("=======\n".to_string());
+    push_side(&mut res.lines, theirs);
+    res.lines.push(marker(">>>>>>>", &options.theirs_label));
+    push_lines(&mut res.lines, suffix);
+}
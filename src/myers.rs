@@ -0,0 +1,203 @@
+use sequencematcher::Match;
+use std::ops::{Index, IndexMut};
+use utils::{common_prefix_len, common_suffix_len};
+
+// Diagonal-indexed vector of furthest reaching x positions, k may be negative.
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> V {
+        V {
+            offset: max_d as isize,
+            v: vec![0; 2 * max_d],
+        }
+    }
+}
+
+impl Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, index: isize) -> &usize {
+        &self.v[(index + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for V {
+    fn index_mut(&mut self, index: isize) -> &mut usize {
+        &mut self.v[(index + self.offset) as usize]
+    }
+}
+
+fn max_d(first_length: usize, second_length: usize) -> usize {
+    (first_length + second_length).div_ceil(2) + 1
+}
+
+/// Returns the blocks of equal elements of a shortest edit script between
+/// the two sequences, found with Myers' O(ND) algorithm in linear space.
+/// Blocks are ordered but not merged and carry no sentinel.
+pub fn matching_blocks<T: PartialEq>(first_sequence: &[T], second_sequence: &[T]) -> Vec<Match> {
+    let max_d = max_d(first_sequence.len(), second_sequence.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    let mut matches = Vec::new();
+    conquer(
+        first_sequence,
+        0,
+        first_sequence.len(),
+        second_sequence,
+        0,
+        second_sequence.len(),
+        &mut vf,
+        &mut vb,
+        &mut matches,
+    );
+    matches
+}
+
+#[allow(clippy::too_many_arguments)]
+fn conquer<T: PartialEq>(
+    first_sequence: &[T],
+    mut first_start: usize,
+    mut first_end: usize,
+    second_sequence: &[T],
+    mut second_start: usize,
+    mut second_end: usize,
+    vf: &mut V,
+    vb: &mut V,
+    matches: &mut Vec<Match>,
+) {
+    let prefix = common_prefix_len(
+        &first_sequence[first_start..first_end],
+        &second_sequence[second_start..second_end],
+    );
+    if prefix > 0 {
+        matches.push(Match {
+            first_start,
+            second_start,
+            size: prefix,
+        });
+    }
+    first_start += prefix;
+    second_start += prefix;
+    let suffix = common_suffix_len(
+        &first_sequence[first_start..first_end],
+        &second_sequence[second_start..second_end],
+    );
+    first_end -= suffix;
+    second_end -= suffix;
+
+    if first_start < first_end && second_start < second_end {
+        let split = find_middle_snake(
+            first_sequence,
+            first_start,
+            first_end,
+            second_sequence,
+            second_start,
+            second_end,
+            vf,
+            vb,
+        );
+        // Without a split point the whole remaining range is a replacement.
+        if let Some((x, y)) = split {
+            conquer(
+                first_sequence,
+                first_start,
+                x,
+                second_sequence,
+                second_start,
+                y,
+                vf,
+                vb,
+                matches,
+            );
+            conquer(
+                first_sequence,
+                x,
+                first_end,
+                second_sequence,
+                y,
+                second_end,
+                vf,
+                vb,
+                matches,
+            );
+        }
+    }
+
+    if suffix > 0 {
+        matches.push(Match {
+            first_start: first_end,
+            second_start: second_end,
+            size: suffix,
+        });
+    }
+}
+
+// Finds the middle snake of the edit graph and returns the point where the
+// problem is split in two. Both ranges must be non-empty and have no common
+// prefix or suffix, in which case the two paths always meet before d_max.
+#[allow(clippy::too_many_arguments)]
+fn find_middle_snake<T: PartialEq>(
+    first_sequence: &[T],
+    first_start: usize,
+    first_end: usize,
+    second_sequence: &[T],
+    second_start: usize,
+    second_end: usize,
+    vf: &mut V,
+    vb: &mut V,
+) -> Option<(usize, usize)> {
+    let first = &first_sequence[first_start..first_end];
+    let second = &second_sequence[second_start..second_end];
+    let (n, m) = (first.len(), second.len());
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    vf[1] = 0;
+    vb[1] = 0;
+    let d_max = max_d(n, m) as isize;
+    for d in 0..d_max {
+        // Forward path.
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += common_prefix_len(&first[x..], &second[y..]);
+            }
+            vf[k] = x;
+            if odd && (k - delta).abs() < d && vf[k] + vb[-(k - delta)] >= n {
+                return Some((first_start + x0, second_start + y0));
+            }
+            k -= 2;
+        }
+        // Backward path.
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let advance = common_suffix_len(&first[..n - x], &second[..m - y]);
+                x += advance;
+                y += advance;
+            }
+            vb[k] = x;
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[-(k - delta)] >= n {
+                return Some((first_start + n - x, second_start + m - y));
+            }
+            k -= 2;
+        }
+    }
+    None
+}
@@ -0,0 +1,911 @@
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use utils::{format_range_context, format_range_unified};
+
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+const CONTEXT_HUNK_START: &str = "***************";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line of the patch text where parsing failed.
+    pub line: usize,
+    /// 1-based column (in characters) within that line.
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, column: usize, message: &str) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Format {
+    #[default]
+    Unified,
+    Context,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HunkLine {
+    pub kind: LineKind,
+    /// Text of the line including its terminator. A line without a trailing
+    /// newline is written with a "\ No newline at end of file" marker.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// Start of the hunk in the original file as written in the header:
+    /// 1-based, or the line before the hunk when `from_count` is zero.
+    pub from_start: usize,
+    pub from_count: usize,
+    pub to_start: usize,
+    pub to_count: usize,
+    /// Anything following the closing `@@`, such as a function name.
+    pub section: String,
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    /// Lines preceding the file header, such as `diff --git` lines.
+    pub header: Vec<String>,
+    /// Format the patch was parsed from and is written back in.
+    pub format: Format,
+    pub from_file: String,
+    /// Text after the tab following the file name, `None` if there is no tab.
+    pub from_file_date: Option<String>,
+    pub to_file: String,
+    pub to_file_date: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Patch {
+    pub files: Vec<FilePatch>,
+}
+
+impl Patch {
+    /// Parses unified or context diff text. Lines that don't belong to a file patch are
+    /// kept as the header of the following file, trailing ones are ignored.
+    pub fn parse(text: &str) -> Result<Patch, ParseError> {
+        Parser::new(text).parse()
+    }
+
+    /// Returns the patch that undoes this one.
+    pub fn invert(&self) -> Patch {
+        Patch {
+            files: self.files.iter().map(FilePatch::invert).collect(),
+        }
+    }
+}
+
+impl FromStr for Patch {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Patch, ParseError> {
+        Patch::parse(text)
+    }
+}
+
+/// Settings for `FilePatch::apply_with_options`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyOptions {
+    /// Number of context lines that may be ignored at each end of a hunk,
+    /// like the fuzz factor of GNU patch.
+    pub fuzz: usize,
+    /// How far a hunk may move from where the previous hunks put it, `None`
+    /// to search the whole sequence.
+    pub max_offset: Option<usize>,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> ApplyOptions {
+        ApplyOptions::new()
+    }
+}
+
+impl ApplyOptions {
+    /// GNU patch defaults: fuzz factor 2 and no limit on the offset.
+    pub fn new() -> ApplyOptions {
+        ApplyOptions {
+            fuzz: 2,
+            max_offset: None,
+        }
+    }
+
+    /// Every hunk has to match exactly at the lines named in its header.
+    pub fn strict() -> ApplyOptions {
+        ApplyOptions {
+            fuzz: 0,
+            max_offset: Some(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkResult {
+    Applied,
+    /// Applied `offset` lines away from the position in the hunk header.
+    AppliedWithOffset {
+        offset: isize,
+    },
+    /// Applied after ignoring up to `fuzz` context lines at each end.
+    AppliedWithFuzz {
+        offset: isize,
+        fuzz: usize,
+    },
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyResult {
+    /// The patched sequence. Rejected hunks leave their lines untouched.
+    pub lines: Vec<String>,
+    /// One entry per hunk, in patch order.
+    pub hunks: Vec<HunkResult>,
+}
+
+impl ApplyResult {
+    pub fn is_clean(&self) -> bool {
+        !self.hunks.contains(&HunkResult::Rejected)
+    }
+}
+
+impl FilePatch {
+    /// Swaps the two sides of the patch. Header lines are kept as they are.
+    pub fn invert(&self) -> FilePatch {
+        FilePatch {
+            header: self.header.clone(),
+            format: self.format,
+            from_file: self.to_file.clone(),
+            from_file_date: self.to_file_date.clone(),
+            to_file: self.from_file.clone(),
+            to_file_date: self.from_file_date.clone(),
+            hunks: self.hunks.iter().map(Hunk::invert).collect(),
+        }
+    }
+
+    /// Applies the patch with `ApplyOptions::strict`.
+    pub fn apply(&self, lines: &[&str]) -> ApplyResult {
+        self.apply_with_options(lines, &ApplyOptions::strict())
+    }
+
+    pub fn apply_with_options(&self, lines: &[&str], options: &ApplyOptions) -> ApplyResult {
+        let mut res = Vec::with_capacity(lines.len());
+        let mut results = Vec::with_capacity(self.hunks.len());
+        // First line not copied to the result yet, hunks can't go back past it.
+        let mut position = 0;
+        let mut last_offset = 0;
+        for hunk in &self.hunks {
+            let old = hunk.old_lines();
+            let new = hunk.new_lines();
+            let placement = match hunk.locate(&old, lines, position, last_offset, options) {
+                Some(placement) => placement,
+                None => {
+                    results.push(HunkResult::Rejected);
+                    continue;
+                }
+            };
+            res.extend(
+                lines[position..placement.start]
+                    .iter()
+                    .map(|line| line.to_string()),
+            );
+            let new = &new[placement.prefix..new.len() - placement.suffix];
+            res.extend(new.iter().map(|line| line.to_string()));
+            position = placement.start + old.len() - placement.prefix - placement.suffix;
+            last_offset = placement.offset;
+            results.push(match (placement.offset, placement.fuzz) {
+                (0, 0) => HunkResult::Applied,
+                (offset, 0) => HunkResult::AppliedWithOffset { offset },
+                (offset, fuzz) => HunkResult::AppliedWithFuzz { offset, fuzz },
+            });
+        }
+        res.extend(lines[position..].iter().map(|line| line.to_string()));
+        ApplyResult {
+            lines: res,
+            hunks: results,
+        }
+    }
+}
+
+struct Placement {
+    start: usize,
+    offset: isize,
+    fuzz: usize,
+    // Context lines ignored at the beginning and the end of the hunk.
+    prefix: usize,
+    suffix: usize,
+}
+
+impl Hunk {
+    /// Swaps the two sides of the hunk, keeping removed lines ahead of added
+    /// ones within each change.
+    pub fn invert(&self) -> Hunk {
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut added = Vec::new();
+        for line in &self.lines {
+            match line.kind {
+                LineKind::Context => {
+                    lines.append(&mut added);
+                    lines.push(line.clone());
+                }
+                LineKind::Removed => added.push(HunkLine {
+                    kind: LineKind::Added,
+                    text: line.text.clone(),
+                }),
+                LineKind::Added => lines.push(HunkLine {
+                    kind: LineKind::Removed,
+                    text: line.text.clone(),
+                }),
+            }
+        }
+        lines.append(&mut added);
+        Hunk {
+            from_start: self.to_start,
+            from_count: self.to_count,
+            to_start: self.from_start,
+            to_count: self.from_count,
+            section: self.section.clone(),
+            lines,
+        }
+    }
+
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter(|line| line.kind != LineKind::Added)
+            .map(|line| line.text.as_str())
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter(|line| line.kind != LineKind::Removed)
+            .map(|line| line.text.as_str())
+            .collect()
+    }
+
+    // Finds where the old side of the hunk occurs in `lines`, trying larger
+    // fuzz factors only when no offset works with the smaller ones. Like GNU
+    // patch, a hunk with less context on one side than on the other is taken
+    // to touch that end of the file unless fuzz makes up for the difference.
+    fn locate(
+        &self,
+        old: &[&str],
+        lines: &[&str],
+        position: usize,
+        last_offset: isize,
+        options: &ApplyOptions,
+    ) -> Option<Placement> {
+        let leading = self
+            .lines
+            .iter()
+            .take_while(|line| line.kind == LineKind::Context)
+            .count();
+        let trailing = self
+            .lines
+            .iter()
+            .rev()
+            .take_while(|line| line.kind == LineKind::Context)
+            .count();
+        let context = cmp::max(leading, trailing);
+        let header_start = to_begin(self.from_start, self.from_count) as isize;
+        for fuzz in 0..=cmp::min(options.fuzz, context) {
+            let prefix_fuzz = (fuzz + leading) as isize - context as isize;
+            let suffix_fuzz = (fuzz + trailing) as isize - context as isize;
+            let prefix = cmp::max(prefix_fuzz, 0) as usize;
+            let suffix = cmp::max(suffix_fuzz, 0) as usize;
+            // Ignoring every line would let the hunk match anywhere.
+            if prefix + suffix >= old.len() && !old.is_empty() {
+                break;
+            }
+            let pattern = &old[prefix..old.len() - suffix];
+            let expected = header_start + last_offset + prefix as isize;
+            let mut min_start = position as isize;
+            let mut max_start = lines.len() as isize - pattern.len() as isize;
+            if prefix_fuzz < 0 {
+                max_start = cmp::min(max_start, 0);
+            }
+            if suffix_fuzz < 0 {
+                min_start = cmp::max(min_start, max_start);
+            }
+            let max_distance = cmp::max(expected - min_start, max_start - expected);
+            let max_distance = match options.max_offset {
+                Some(max_offset) => cmp::min(max_offset as isize, max_distance),
+                None => max_distance,
+            };
+            for distance in 0..=max_distance {
+                for &start in &[expected + distance, expected - distance] {
+                    if start < min_start || start > max_start {
+                        continue;
+                    }
+                    let start = start as usize;
+                    if lines[start..start + pattern.len()] == *pattern {
+                        return Some(Placement {
+                            start,
+                            offset: start as isize - header_start - prefix as isize,
+                            fuzz,
+                            prefix,
+                            suffix,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in &self.files {
+            write!(f, "{}", file)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FilePatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.header {
+            f.write_str(line)?;
+        }
+        let (from_marker, to_marker) = match self.format {
+            Format::Unified => ("---", "+++"),
+            Format::Context => ("***", "---"),
+        };
+        write_file_header(f, from_marker, &self.from_file, &self.from_file_date)?;
+        write_file_header(f, to_marker, &self.to_file, &self.to_file_date)?;
+        for hunk in &self.hunks {
+            match self.format {
+                Format::Unified => write!(f, "{}", hunk)?,
+                Format::Context => write_context_hunk(f, hunk)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Ranges are stored as written, convert back to the 0-based
+        // half-open form expected by format_range_unified.
+        let from_begin = to_begin(self.from_start, self.from_count);
+        let to_begin = to_begin(self.to_start, self.to_count);
+        writeln!(
+            f,
+            "@@ -{} +{} @@{}",
+            format_range_unified(from_begin, from_begin + self.from_count),
+            format_range_unified(to_begin, to_begin + self.to_count),
+            self.section
+        )?;
+        for line in &self.lines {
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HunkLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = match self.kind {
+            LineKind::Context => " ",
+            LineKind::Removed => "-",
+            LineKind::Added => "+",
+        };
+        write_line(f, prefix, &self.text)
+    }
+}
+
+fn write_line(f: &mut fmt::Formatter, prefix: &str, text: &str) -> fmt::Result {
+    write!(f, "{}{}", prefix, text)?;
+    if !text.ends_with('\n') {
+        writeln!(f, "\n{}", NO_NEWLINE_MARKER)?;
+    }
+    Ok(())
+}
+
+fn write_context_hunk(f: &mut fmt::Formatter, hunk: &Hunk) -> fmt::Result {
+    writeln!(f, "***************{}", hunk.section)?;
+    let from_begin = to_begin(hunk.from_start, hunk.from_count);
+    writeln!(
+        f,
+        "*** {} ****",
+        format_range_context(from_begin, from_begin + hunk.from_count)
+    )?;
+    let runs = change_runs(&hunk.lines);
+    if hunk.lines.iter().any(|line| line.kind == LineKind::Removed) {
+        for (line, &(removed, added)) in hunk.lines.iter().zip(&runs) {
+            match line.kind {
+                LineKind::Context => write_line(f, "  ", &line.text)?,
+                LineKind::Removed if removed && added => write_line(f, "! ", &line.text)?,
+                LineKind::Removed => write_line(f, "- ", &line.text)?,
+                LineKind::Added => {}
+            }
+        }
+    }
+    let to_begin = to_begin(hunk.to_start, hunk.to_count);
+    writeln!(
+        f,
+        "--- {} ----",
+        format_range_context(to_begin, to_begin + hunk.to_count)
+    )?;
+    if hunk.lines.iter().any(|line| line.kind == LineKind::Added) {
+        for (line, &(removed, added)) in hunk.lines.iter().zip(&runs) {
+            match line.kind {
+                LineKind::Context => write_line(f, "  ", &line.text)?,
+                LineKind::Added if removed && added => write_line(f, "! ", &line.text)?,
+                LineKind::Added => write_line(f, "+ ", &line.text)?,
+                LineKind::Removed => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+// For every line, whether the run of changed lines it belongs to contains
+// removed and added lines. Runs with both are written as `!` changes.
+fn change_runs(lines: &[HunkLine]) -> Vec<(bool, bool)> {
+    let mut runs = Vec::with_capacity(lines.len());
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        let (mut removed, mut added) = (false, false);
+        while end < lines.len() && lines[end].kind != LineKind::Context {
+            removed |= lines[end].kind == LineKind::Removed;
+            added |= lines[end].kind == LineKind::Added;
+            end += 1;
+        }
+        let end = cmp::max(end, start + 1);
+        for _ in start..end {
+            runs.push((removed, added));
+        }
+        start = end;
+    }
+    runs
+}
+
+fn write_file_header(
+    f: &mut fmt::Formatter,
+    marker: &str,
+    file: &str,
+    date: &Option<String>,
+) -> fmt::Result {
+    match *date {
+        Some(ref date) => writeln!(f, "{} {}\t{}", marker, file, date),
+        None => writeln!(f, "{} {}", marker, file),
+    }
+}
+
+fn to_begin(start: usize, count: usize) -> usize {
+    if count == 0 {
+        start
+    } else {
+        start.saturating_sub(1)
+    }
+}
+
+fn strip_line_end(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Parser<'a> {
+        Parser {
+            lines: text.split_inclusive('\n').collect(),
+            position: 0,
+        }
+    }
+
+    fn error(&self, column: usize, message: &str) -> ParseError {
+        ParseError::new(self.position + 1, column, message)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.lines.get(self.position).cloned()
+    }
+
+    fn parse(mut self) -> Result<Patch, ParseError> {
+        let mut patch = Patch::default();
+        let mut header = Vec::new();
+        while let Some(line) = self.peek() {
+            if let Some(format) = self.file_start() {
+                let file = self.parse_file(header, format)?;
+                patch.files.push(file);
+                header = Vec::new();
+            } else {
+                header.push(line.to_string());
+                self.position += 1;
+            }
+        }
+        Ok(patch)
+    }
+
+    fn file_start(&self) -> Option<Format> {
+        let line = self.peek()?;
+        let next = self.lines.get(self.position + 1)?;
+        if line.starts_with("--- ") && next.starts_with("+++ ") {
+            Some(Format::Unified)
+        } else if line.starts_with("*** ") && next.starts_with("--- ") {
+            Some(Format::Context)
+        } else {
+            None
+        }
+    }
+
+    fn parse_file(&mut self, header: Vec<String>, format: Format) -> Result<FilePatch, ParseError> {
+        let (from_file, from_file_date) = parse_file_header(self.peek().unwrap());
+        self.position += 1;
+        let (to_file, to_file_date) = parse_file_header(self.peek().unwrap());
+        self.position += 1;
+        let mut hunks = Vec::new();
+        while let Some(line) = self.peek() {
+            let hunk = match format {
+                Format::Unified if line.starts_with("@@") => self.parse_hunk()?,
+                Format::Context if line.starts_with(CONTEXT_HUNK_START) => {
+                    self.parse_context_hunk()?
+                }
+                _ => break,
+            };
+            hunks.push(hunk);
+        }
+        if hunks.is_empty() {
+            return Err(self.error(1, "expected hunk header"));
+        }
+        Ok(FilePatch {
+            header,
+            format,
+            from_file,
+            from_file_date,
+            to_file,
+            to_file_date,
+            hunks,
+        })
+    }
+
+    fn parse_hunk(&mut self) -> Result<Hunk, ParseError> {
+        let line = strip_line_end(self.peek().unwrap());
+        let mut cursor = Cursor::new(line);
+        cursor
+            .expect("@@ -")
+            .map_err(|column| self.error(column, "expected '@@ -'"))?;
+        let (from_start, from_count) = cursor
+            .range()
+            .map_err(|column| self.error(column, "invalid range"))?;
+        cursor
+            .expect(" +")
+            .map_err(|column| self.error(column, "expected ' +'"))?;
+        let (to_start, to_count) = cursor
+            .range()
+            .map_err(|column| self.error(column, "invalid range"))?;
+        cursor
+            .expect(" @@")
+            .map_err(|column| self.error(column, "expected ' @@'"))?;
+        let section = cursor.rest().to_string();
+        self.position += 1;
+
+        let mut lines: Vec<HunkLine> = Vec::new();
+        let (mut from_seen, mut to_seen) = (0, 0);
+        while from_seen < from_count || to_seen < to_count {
+            let line = match self.peek() {
+                Some(line) => line,
+                None => return Err(self.error(1, "unexpected end of hunk")),
+            };
+            let kind = match line.chars().next() {
+                Some(' ') => LineKind::Context,
+                Some('-') => LineKind::Removed,
+                Some('+') => LineKind::Added,
+                // Some tools strip the space off empty context lines.
+                Some('\n') | Some('\r') => LineKind::Context,
+                Some('\\') => {
+                    self.no_newline(&mut lines)?;
+                    continue;
+                }
+                _ => return Err(self.error(1, "expected ' ', '-' or '+'")),
+            };
+            match kind {
+                LineKind::Context => {
+                    from_seen += 1;
+                    to_seen += 1;
+                }
+                LineKind::Removed => from_seen += 1,
+                LineKind::Added => to_seen += 1,
+            }
+            if from_seen > from_count || to_seen > to_count {
+                return Err(self.error(1, "hunk is longer than its header"));
+            }
+            let text = if line.starts_with(' ') || line.starts_with('-') || line.starts_with('+') {
+                &line[1..]
+            } else {
+                line
+            };
+            lines.push(HunkLine {
+                kind,
+                text: text.to_string(),
+            });
+            self.position += 1;
+        }
+        if let Some(line) = self.peek() {
+            if line.starts_with('\\') {
+                self.no_newline(&mut lines)?;
+            }
+        }
+        Ok(Hunk {
+            from_start,
+            from_count,
+            to_start,
+            to_count,
+            section,
+            lines,
+        })
+    }
+
+    fn parse_context_hunk(&mut self) -> Result<Hunk, ParseError> {
+        let section = strip_line_end(self.peek().unwrap())[CONTEXT_HUNK_START.len()..].to_string();
+        self.position += 1;
+        let (from_start, from_end) = self.context_range("*** ", " ****")?;
+        let old = self.context_side(from_start, from_end, &["  ", "- ", "! "])?;
+        let (to_start, to_end) = self.context_range("--- ", " ----")?;
+        let new = self.context_side(to_start, to_end, &["  ", "+ ", "! "])?;
+
+        // A side without changes is left out, rebuild it from the other one.
+        let old = if old.is_empty() {
+            context_only(&new)
+        } else {
+            old
+        };
+        let new = if new.is_empty() {
+            context_only(&old)
+        } else {
+            new
+        };
+        let lines = merge_context_sides(&old, &new)
+            .ok_or_else(|| self.error(1, "context lines of both sides differ"))?;
+        let from_count = old.len();
+        let to_count = new.len();
+        if from_end.is_some_and(|end| end + 1 != from_start + from_count)
+            || to_end.is_some_and(|end| end + 1 != to_start + to_count)
+        {
+            return Err(self.error(1, "hunk does not match its header"));
+        }
+        Ok(Hunk {
+            from_start,
+            from_count,
+            to_start,
+            to_count,
+            section,
+            lines,
+        })
+    }
+
+    // Parses `*** 1,4 ****` or `--- 3 ----`. The end is `None` for a single
+    // number, which stands for either one line or an empty range.
+    fn context_range(
+        &mut self,
+        open: &str,
+        close: &str,
+    ) -> Result<(usize, Option<usize>), ParseError> {
+        let line = match self.peek() {
+            Some(line) => strip_line_end(line),
+            None => return Err(self.error(1, "unexpected end of hunk")),
+        };
+        let mut cursor = Cursor::new(line);
+        cursor
+            .expect(open)
+            .map_err(|column| self.error(column, &format!("expected '{}'", open)))?;
+        let start = cursor
+            .number()
+            .map_err(|column| self.error(column, "invalid range"))?;
+        let end = if cursor.rest().starts_with(',') {
+            cursor.offset += 1;
+            let end = cursor
+                .number()
+                .map_err(|column| self.error(column, "invalid range"))?;
+            Some(end)
+        } else {
+            None
+        };
+        cursor
+            .expect(close)
+            .map_err(|column| self.error(column, &format!("expected '{}'", close)))?;
+        self.position += 1;
+        Ok((start, end))
+    }
+
+    fn context_side(
+        &mut self,
+        start: usize,
+        end: Option<usize>,
+        prefixes: &[&str],
+    ) -> Result<Vec<(char, String)>, ParseError> {
+        let limit = match end {
+            Some(end) => (end + 1).saturating_sub(start),
+            None => 1,
+        };
+        let mut lines: Vec<(char, String)> = Vec::new();
+        while lines.len() < limit {
+            let line = match self.peek() {
+                Some(line) => line,
+                None => break,
+            };
+            if line.starts_with('\\') && !lines.is_empty() {
+                self.no_newline_context(&mut lines)?;
+                continue;
+            }
+            let (tag, text) = if prefixes.iter().any(|prefix| line.starts_with(prefix)) {
+                (line.chars().next().unwrap(), &line[2..])
+            } else if line == "\n" || line == "\r\n" {
+                (' ', line)
+            } else {
+                break;
+            };
+            lines.push((tag, text.to_string()));
+            self.position += 1;
+        }
+        if let Some(line) = self.peek() {
+            if line.starts_with('\\') && !lines.is_empty() {
+                self.no_newline_context(&mut lines)?;
+            }
+        }
+        if !lines.is_empty() && lines.len() < limit {
+            return Err(self.error(1, "unexpected end of hunk"));
+        }
+        Ok(lines)
+    }
+
+    fn no_newline_context(&mut self, lines: &mut [(char, String)]) -> Result<(), ParseError> {
+        let previous = match lines.last_mut() {
+            Some(previous) if previous.1.ends_with('\n') => previous,
+            _ => return Err(self.error(1, "unexpected '\\' line")),
+        };
+        previous.1.pop();
+        if previous.1.ends_with('\r') {
+            previous.1.pop();
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn no_newline(&mut self, lines: &mut [HunkLine]) -> Result<(), ParseError> {
+        let previous = match lines.last_mut() {
+            Some(previous) if previous.text.ends_with('\n') => previous,
+            _ => return Err(self.error(1, "unexpected '\\' line")),
+        };
+        previous.text.pop();
+        if previous.text.ends_with('\r') {
+            previous.text.pop();
+        }
+        self.position += 1;
+        Ok(())
+    }
+}
+
+// Splits a `--- `, `+++ ` or `*** ` line into file name and date.
+fn parse_file_header(line: &str) -> (String, Option<String>) {
+    let line = &strip_line_end(line)[4..];
+    match line.find('\t') {
+        Some(tab) => (line[..tab].to_string(), Some(line[tab + 1..].to_string())),
+        None => (line.to_string(), None),
+    }
+}
+
+fn context_only(lines: &[(char, String)]) -> Vec<(char, String)> {
+    lines.iter().filter(|line| line.0 == ' ').cloned().collect()
+}
+
+// Interleaves the two sides of a context hunk into unified hunk lines, `None`
+// if their unchanged lines don't line up.
+fn merge_context_sides(old: &[(char, String)], new: &[(char, String)]) -> Option<Vec<HunkLine>> {
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let old_tag = old.get(i).map(|line| line.0);
+        let new_tag = new.get(j).map(|line| line.0);
+        if old_tag == Some('-') || old_tag == Some('!') {
+            lines.push(HunkLine {
+                kind: LineKind::Removed,
+                text: old[i].1.clone(),
+            });
+            i += 1;
+        } else if new_tag == Some('+') || new_tag == Some('!') {
+            lines.push(HunkLine {
+                kind: LineKind::Added,
+                text: new[j].1.clone(),
+            });
+            j += 1;
+        } else if old_tag == Some(' ') && new_tag == Some(' ') && old[i].1 == new[j].1 {
+            lines.push(HunkLine {
+                kind: LineKind::Context,
+                text: old[i].1.clone(),
+            });
+            i += 1;
+            j += 1;
+        } else {
+            return None;
+        }
+    }
+    Some(lines)
+}
+
+// Walks a hunk header, errors carry the 1-based column of the failure.
+struct Cursor<'a> {
+    line: &'a str,
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str) -> Cursor<'a> {
+        Cursor { line, offset: 0 }
+    }
+
+    fn column(&self) -> usize {
+        self.line[..self.offset].chars().count() + 1
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.offset..]
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), usize> {
+        if self.rest().starts_with(text) {
+            self.offset += text.len();
+            Ok(())
+        } else {
+            Err(self.column())
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, usize> {
+        let digits = self
+            .rest()
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let number = self.rest()[..digits].parse().map_err(|_| self.column())?;
+        self.offset += digits;
+        Ok(number)
+    }
+
+    fn range(&mut self) -> Result<(usize, usize), usize> {
+        let start = self.number()?;
+        let count = if self.rest().starts_with(',') {
+            self.offset += 1;
+            self.number()?
+        } else {
+            1
+        };
+        Ok((start, count))
+    }
+}
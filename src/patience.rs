@@ -0,0 +1,165 @@
+use myers;
+use sequencematcher::Match;
+use std::collections::HashMap;
+use std::hash::Hash;
+use utils::{common_prefix_len, common_suffix_len};
+
+/// Returns the blocks of equal elements found by patience diff: lines that
+/// occur exactly once on both sides are used as anchors, the longest
+/// increasing run of anchors is kept and the gaps between them are diffed
+/// recursively. Ranges without unique lines fall back to Myers' algorithm.
+/// Blocks are ordered but not merged and carry no sentinel.
+pub fn matching_blocks<T: Hash + Eq>(first_sequence: &[T], second_sequence: &[T]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    patience(
+        first_sequence,
+        0,
+        first_sequence.len(),
+        second_sequence,
+        0,
+        second_sequence.len(),
+        &mut matches,
+    );
+    matches
+}
+
+fn patience<T: Hash + Eq>(
+    first_sequence: &[T],
+    mut first_start: usize,
+    mut first_end: usize,
+    second_sequence: &[T],
+    mut second_start: usize,
+    mut second_end: usize,
+    matches: &mut Vec<Match>,
+) {
+    let prefix = common_prefix_len(
+        &first_sequence[first_start..first_end],
+        &second_sequence[second_start..second_end],
+    );
+    if prefix > 0 {
+        matches.push(Match {
+            first_start,
+            second_start,
+            size: prefix,
+        });
+    }
+    first_start += prefix;
+    second_start += prefix;
+    let suffix = common_suffix_len(
+        &first_sequence[first_start..first_end],
+        &second_sequence[second_start..second_end],
+    );
+    first_end -= suffix;
+    second_end -= suffix;
+
+    if first_start < first_end && second_start < second_end {
+        let anchors = unique_anchors(
+            &first_sequence[first_start..first_end],
+            &second_sequence[second_start..second_end],
+        );
+        if anchors.is_empty() {
+            let blocks = myers::matching_blocks(
+                &first_sequence[first_start..first_end],
+                &second_sequence[second_start..second_end],
+            );
+            matches.extend(blocks.into_iter().map(|m| Match {
+                first_start: m.first_start + first_start,
+                second_start: m.second_start + second_start,
+                size: m.size,
+            }));
+        } else {
+            let (mut i, mut j) = (first_start, second_start);
+            for (anchor_i, anchor_j) in anchors {
+                let (anchor_i, anchor_j) = (anchor_i + first_start, anchor_j + second_start);
+                patience(
+                    first_sequence,
+                    i,
+                    anchor_i,
+                    second_sequence,
+                    j,
+                    anchor_j,
+                    matches,
+                );
+                matches.push(Match {
+                    first_start: anchor_i,
+                    second_start: anchor_j,
+                    size: 1,
+                });
+                i = anchor_i + 1;
+                j = anchor_j + 1;
+            }
+            patience(
+                first_sequence,
+                i,
+                first_end,
+                second_sequence,
+                j,
+                second_end,
+                matches,
+            );
+        }
+    }
+
+    if suffix > 0 {
+        matches.push(Match {
+            first_start: first_end,
+            second_start: second_end,
+            size: suffix,
+        });
+    }
+}
+
+// Pairs of positions of elements that are unique in both sequences, reduced
+// to the longest run that is increasing on both sides.
+fn unique_anchors<T: Hash + Eq>(
+    first_sequence: &[T],
+    second_sequence: &[T],
+) -> Vec<(usize, usize)> {
+    let mut occurrences: HashMap<&T, (usize, usize, usize)> = HashMap::new();
+    for (i, item) in first_sequence.iter().enumerate() {
+        let entry = occurrences.entry(item).or_insert((0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    let mut pairs = Vec::new();
+    for (j, item) in second_sequence.iter().enumerate() {
+        if let Some(entry) = occurrences.get_mut(item) {
+            entry.1 += 1;
+            if entry.1 == 1 {
+                pairs.push((entry.2, j));
+            }
+        }
+    }
+    pairs.retain(|&(i, _)| {
+        let &(first_count, second_count, _) = occurrences.get(&first_sequence[i]).unwrap();
+        first_count == 1 && second_count == 1
+    });
+    pairs.sort();
+    longest_increasing_run(&pairs)
+}
+
+// Patience sorting: `pairs` is ordered by the first position, find the
+// longest subsequence whose second positions are increasing too.
+fn longest_increasing_run(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut tops: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+    for (index, &(_, j)) in pairs.iter().enumerate() {
+        let pile = match tops.binary_search_by(|&top| pairs[top].1.cmp(&j)) {
+            Ok(pile) | Err(pile) => pile,
+        };
+        previous.push(if pile > 0 { Some(tops[pile - 1]) } else { None });
+        if pile == tops.len() {
+            tops.push(index);
+        } else {
+            tops[pile] = index;
+        }
+    }
+    let mut res = Vec::new();
+    let mut current = tops.last().cloned();
+    while let Some(index) = current {
+        res.push(pairs[index]);
+        current = previous[index];
+    }
+    res.reverse();
+    res
+}
@@ -1,6 +1,12 @@
+use histogram;
+use myers;
+use patience;
+use std::borrow::Cow;
 use std::cmp::{max, min};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::hash::Hash;
+use std::sync::Arc;
 use utils::calculate_ratio;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
@@ -20,9 +26,29 @@ impl Match {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tag {
+    Replace,
+    Delete,
+    Insert,
+    Equal,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Tag::Replace => "replace",
+            Tag::Delete => "delete",
+            Tag::Insert => "insert",
+            Tag::Equal => "equal",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Opcode {
-    pub tag: String,
+    pub tag: Tag,
     pub first_start: usize,
     pub first_end: usize,
     pub second_start: usize,
@@ -31,7 +57,7 @@ pub struct Opcode {
 
 impl Opcode {
     fn new(
-        tag: String,
+        tag: Tag,
         first_start: usize,
         first_end: usize,
         second_start: usize,
@@ -47,6 +73,18 @@ impl Opcode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    #[default]
+    RatcliffObershelp,
+    Myers,
+    Patience,
+    Histogram,
+}
+
+type JunkFn<'a, T> = Box<dyn Fn(&T) -> bool + Send + Sync + 'a>;
+type SharedJunkFn<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+
 pub trait Sequence: Eq + Hash {}
 impl<T: Eq + Hash> Sequence for T {}
 
@@ -55,12 +93,26 @@ pub struct SequenceMatcher<'a, T: 'a + Sequence> {
     second_sequence: &'a [T],
     matching_blocks: Option<Vec<Match>>,
     opcodes: Option<Vec<Opcode>>,
-    is_junk: Option<fn(&T) -> bool>,
-    second_sequence_elements: HashMap<&'a T, Vec<usize>>,
+    is_junk: Option<JunkFn<'a, T>>,
+    autojunk: bool,
+    algorithm: Algorithm,
+    index: Cow<'a, Index<'a, T>>,
+    second_sequence_counts: Option<HashMap<&'a T, usize>>,
 }
 
 impl<'a, T: Sequence> SequenceMatcher<'a, T> {
     pub fn new<S>(first_sequence: &'a S, second_sequence: &'a S) -> SequenceMatcher<'a, T>
+    where
+        S: AsRef<[T]> + ?Sized,
+    {
+        SequenceMatcher::with_autojunk(first_sequence, second_sequence, true)
+    }
+
+    pub fn with_autojunk<S>(
+        first_sequence: &'a S,
+        second_sequence: &'a S,
+        autojunk: bool,
+    ) -> SequenceMatcher<'a, T>
     where
         S: AsRef<[T]> + ?Sized,
     {
@@ -70,19 +122,54 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
             matching_blocks: None,
             opcodes: None,
             is_junk: None,
-            second_sequence_elements: HashMap::new(),
+            autojunk,
+            algorithm: Algorithm::default(),
+            index: Cow::Owned(Index::empty()),
+            second_sequence_counts: None,
         };
         matcher.set_seqs(first_sequence, second_sequence);
         matcher
     }
 
-    pub fn set_is_junk(&mut self, is_junk: Option<fn(&T) -> bool>) {
-        self.is_junk = is_junk;
+    /// Matches `first_sequence` against the sequence `index` was built for,
+    /// with the junk and popular elements found then. Building the index is
+    /// the expensive part of setting up a matcher, so one index can serve
+    /// many matchers comparing different sequences against the same one.
+    pub fn with_index<S>(first_sequence: &'a S, index: &'a Index<'a, T>) -> SequenceMatcher<'a, T>
+    where
+        S: AsRef<[T]> + ?Sized,
+    {
+        SequenceMatcher {
+            first_sequence: first_sequence.as_ref(),
+            second_sequence: index.sequence,
+            matching_blocks: None,
+            opcodes: None,
+            is_junk: None,
+            autojunk: index.autojunk,
+            algorithm: Algorithm::default(),
+            index: Cow::Borrowed(index),
+            second_sequence_counts: None,
+        }
+    }
+
+    pub fn set_is_junk<F>(&mut self, is_junk: Option<F>)
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'a,
+    {
+        self.is_junk = is_junk.map(|f| Box::new(f) as JunkFn<'a, T>);
         self.matching_blocks = None;
         self.opcodes = None;
         self.chain_second_seq();
     }
 
+    /// Selects the algorithm used to find matching blocks. Junk and popular
+    /// elements only affect `Algorithm::RatcliffObershelp`.
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        self.algorithm = algorithm;
+        self.matching_blocks = None;
+        self.opcodes = None;
+    }
+
     pub fn set_seqs<S>(&mut self, first_sequence: &'a S, second_sequence: &'a S)
     where
         S: AsRef<[T]> + ?Sized,
@@ -107,34 +194,25 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
         self.second_sequence = sequence.as_ref();
         self.matching_blocks = None;
         self.opcodes = None;
+        self.second_sequence_counts = None;
         self.chain_second_seq();
     }
 
     fn chain_second_seq(&mut self) {
-        let second_sequence = self.second_sequence;
-        let mut second_sequence_elements = HashMap::new();
-        for (i, item) in second_sequence.iter().enumerate() {
-            let mut counter = second_sequence_elements
-                .entry(item)
-                .or_insert_with(Vec::new);
-            counter.push(i);
-        }
-        if let Some(junk_func) = self.is_junk {
-            second_sequence_elements = second_sequence_elements
-                .into_iter()
-                .filter(|&(element, _)| !junk_func(element))
-                .collect();
-        }
-        // Filter out popular elements
-        let len = second_sequence.len();
-        if len >= 200 {
-            let test_len = (len as f32 / 100.0).floor() as usize + 1;
-            second_sequence_elements = second_sequence_elements
-                .into_iter()
-                .filter(|&(_, ref indexes)| indexes.len() > test_len)
-                .collect();
-        }
-        self.second_sequence_elements = second_sequence_elements;
+        let index = Index::with_junk(self.second_sequence, self.is_junk.as_ref(), self.autojunk);
+        self.index = Cow::Owned(index);
+    }
+
+    pub fn second_sequence_junk(&self) -> &HashSet<&'a T> {
+        &self.index.junk
+    }
+
+    pub fn second_sequence_popular(&self) -> &HashSet<&'a T> {
+        &self.index.popular
+    }
+
+    fn is_second_junk(&self, index: usize) -> bool {
+        self.index.junk.contains(&self.second_sequence[index])
     }
 
     pub fn find_longest_match(
@@ -146,7 +224,7 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
     ) -> Match {
         let first_sequence = &self.first_sequence;
         let second_sequence = &self.second_sequence;
-        let second_sequence_elements = &self.second_sequence_elements;
+        let second_sequence_elements = &self.index.elements;
         let (mut best_i, mut best_j, mut best_size) = (first_start, second_start, 0);
         let mut j2len: HashMap<usize, usize> = HashMap::new();
         for (i, item) in first_sequence
@@ -182,10 +260,13 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
             }
             j2len = new_j2len;
         }
-        for _ in 0..2 {
+        // Extend the match with equal non-junk elements on both sides, then
+        // soak up any equal junk elements adjacent to it.
+        for &junk in &[false, true] {
             while best_i > first_start
                 && best_j > second_start
-                && first_sequence.get(best_i - 1) == second_sequence.get(best_j - 1)
+                && self.is_second_junk(best_j - 1) == junk
+                && first_sequence[best_i - 1] == second_sequence[best_j - 1]
             {
                 best_i -= 1;
                 best_j -= 1;
@@ -193,7 +274,8 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
             }
             while best_i + best_size < first_end
                 && best_j + best_size < second_end
-                && first_sequence.get(best_i + best_size) == second_sequence.get(best_j + best_size)
+                && self.is_second_junk(best_j + best_size) == junk
+                && first_sequence[best_i + best_size] == second_sequence[best_j + best_size]
             {
                 best_size += 1;
             }
@@ -205,6 +287,41 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
         if self.matching_blocks.as_ref().is_some() {
             return self.matching_blocks.as_ref().unwrap().clone();
         }
+        let (first_length, second_length) = (self.first_sequence.len(), self.second_sequence.len());
+        let mut matches = match self.algorithm {
+            Algorithm::RatcliffObershelp => self.longest_matches(),
+            Algorithm::Myers => myers::matching_blocks(self.first_sequence, self.second_sequence),
+            Algorithm::Patience => {
+                patience::matching_blocks(self.first_sequence, self.second_sequence)
+            }
+            Algorithm::Histogram => {
+                histogram::matching_blocks(self.first_sequence, self.second_sequence)
+            }
+        };
+        matches.sort();
+        let (mut first_start, mut second_start, mut size) = (0, 0, 0);
+        let mut non_adjacent = Vec::new();
+        for m in &matches {
+            if first_start + size == m.first_start && second_start + size == m.second_start {
+                size += m.size
+            } else {
+                if size != 0 {
+                    non_adjacent.push(Match::new(first_start, second_start, size));
+                }
+                first_start = m.first_start;
+                second_start = m.second_start;
+                size = m.size;
+            }
+        }
+        if size != 0 {
+            non_adjacent.push(Match::new(first_start, second_start, size));
+        }
+        non_adjacent.push(Match::new(first_length, second_length, 0));
+        self.matching_blocks = Some(non_adjacent);
+        self.matching_blocks.as_ref().unwrap().clone()
+    }
+
+    fn longest_matches(&self) -> Vec<Match> {
         let (first_length, second_length) = (self.first_sequence.len(), self.second_sequence.len());
         let mut matches = Vec::new();
         let mut queue = vec![(0, first_length, 0, second_length)];
@@ -229,118 +346,591 @@ impl<'a, T: Sequence> SequenceMatcher<'a, T> {
                 }
             }
         }
-        matches.sort_by(|a, b| a.cmp(b));
-        let (mut first_start, mut second_start, mut size) = (0, 0, 0);
-        let mut non_adjacent = Vec::new();
-        for m in &matches {
-            if first_start + size == m.first_start && second_start + size == m.second_start {
-                size += m.size
-            } else {
-                if size != 0 {
-                    non_adjacent.push(Match::new(first_start, second_start, size));
-                }
-                first_start = m.first_start;
-                second_start = m.second_start;
-                size = m.size;
+        matches
+    }
+
+    pub fn get_opcodes(&mut self) -> Vec<Opcode> {
+        if self.opcodes.as_ref().is_none() {
+            self.opcodes = Some(opcodes_from_blocks(&self.get_matching_blocks()));
+        }
+        self.opcodes.as_ref().unwrap().clone()
+    }
+
+    pub fn get_grouped_opcodes(&mut self, n: usize) -> Vec<Vec<Opcode>> {
+        group_opcodes(self.get_opcodes(), n)
+    }
+
+    pub fn ratio(&mut self) -> f32 {
+        let matching_blocks = self.get_matching_blocks();
+        matching_ratio(
+            &matching_blocks,
+            self.first_sequence.len(),
+            self.second_sequence.len(),
+        )
+    }
+
+    pub fn quick_ratio(&mut self) -> f32 {
+        if self.second_sequence_counts.is_none() {
+            let mut counts = HashMap::new();
+            for item in self.second_sequence {
+                *counts.entry(item).or_insert(0) += 1;
             }
+            self.second_sequence_counts = Some(counts);
         }
-        if size != 0 {
-            non_adjacent.push(Match::new(first_start, second_start, size));
+        let counts = self.second_sequence_counts.as_ref().unwrap();
+        let mut available: HashMap<&T, isize> = HashMap::new();
+        let mut matches = 0;
+        for item in self.first_sequence {
+            let count = available
+                .entry(item)
+                .or_insert_with(|| counts.get(item).cloned().unwrap_or(0) as isize);
+            if *count > 0 {
+                matches += 1;
+            }
+            *count -= 1;
         }
-        non_adjacent.push(Match::new(first_length, second_length, 0));
-        self.matching_blocks = Some(non_adjacent);
-        self.matching_blocks.as_ref().unwrap().clone()
+        calculate_ratio(
+            matches,
+            self.first_sequence.len() + self.second_sequence.len(),
+        )
     }
 
-    pub fn get_opcodes(&mut self) -> Vec<Opcode> {
-        if self.opcodes.as_ref().is_some() {
-            return self.opcodes.as_ref().unwrap().clone();
+    pub fn real_quick_ratio(&self) -> f32 {
+        length_ratio(self.first_sequence.len(), self.second_sequence.len())
+    }
+}
+
+/// Positions of the elements of a sequence, with junk and popular elements
+/// left out, as `SequenceMatcher` builds it for its second sequence. It is
+/// `Send` and `Sync` when `T` is `Sync`, so one index can be shared by
+/// matchers on several threads, see `SequenceMatcher::with_index`.
+pub struct Index<'a, T: 'a + Sequence> {
+    sequence: &'a [T],
+    elements: HashMap<&'a T, Vec<usize>>,
+    junk: HashSet<&'a T>,
+    popular: HashSet<&'a T>,
+    autojunk: bool,
+}
+
+impl<'a, T: Sequence> Clone for Index<'a, T> {
+    fn clone(&self) -> Index<'a, T> {
+        Index {
+            sequence: self.sequence,
+            elements: self.elements.clone(),
+            junk: self.junk.clone(),
+            popular: self.popular.clone(),
+            autojunk: self.autojunk,
+        }
+    }
+}
+
+impl<'a, T: Sequence> Index<'a, T> {
+    pub fn new<S>(sequence: &'a S) -> Index<'a, T>
+    where
+        S: AsRef<[T]> + ?Sized,
+    {
+        Index::with_junk(sequence, None::<fn(&T) -> bool>, true)
+    }
+
+    /// Leaves out elements for which `is_junk` is true and, with `autojunk`,
+    /// elements making up more than 1% of a sequence of 200 or more.
+    pub fn with_junk<S, F>(sequence: &'a S, is_junk: Option<F>, autojunk: bool) -> Index<'a, T>
+    where
+        S: AsRef<[T]> + ?Sized,
+        F: Fn(&T) -> bool,
+    {
+        let sequence = sequence.as_ref();
+        let mut elements = HashMap::new();
+        for (i, item) in sequence.iter().enumerate() {
+            let counter = elements.entry(item).or_insert_with(Vec::new);
+            counter.push(i);
         }
-        let mut opcodes = Vec::new();
-        let (mut i, mut j) = (0, 0);
-        for m in self.get_matching_blocks() {
-            let mut tag = String::new();
-            if i < m.first_start && j < m.second_start {
-                tag = String::from("replace");
-            } else if i < m.first_start {
-                tag = String::from("delete");
-            } else if j < m.second_start {
-                tag = String::from("insert");
+        let mut junk = HashSet::new();
+        if let Some(junk_func) = is_junk {
+            for element in elements.keys() {
+                if junk_func(element) {
+                    junk.insert(*element);
+                }
             }
-            if !tag.is_empty() {
-                opcodes.push(Opcode::new(tag, i, m.first_start, j, m.second_start));
+            for element in &junk {
+                elements.remove(element);
+            }
+        }
+        // Filter out popular elements
+        let mut popular = HashSet::new();
+        let len = sequence.len();
+        if autojunk && len >= 200 {
+            let test_len = len / 100 + 1;
+            for (element, indexes) in &elements {
+                if indexes.len() > test_len {
+                    popular.insert(*element);
+                }
             }
-            i = m.first_start + m.size;
-            j = m.second_start + m.size;
-            if m.size != 0 {
-                opcodes.push(Opcode::new(
-                    String::from("equal"),
-                    m.first_start,
-                    i,
-                    m.second_start,
-                    j,
-                ));
+            for element in &popular {
+                elements.remove(element);
             }
         }
-        self.opcodes = Some(opcodes);
-        self.opcodes.as_ref().unwrap().clone()
+        Index {
+            sequence,
+            elements,
+            junk,
+            popular,
+            autojunk,
+        }
     }
 
-    pub fn get_grouped_opcodes(&mut self, n: usize) -> Vec<Vec<Opcode>> {
-        let mut res = Vec::new();
-        let mut codes = self.get_opcodes();
-        if codes.is_empty() {
-            codes.push(Opcode::new("equal".to_string(), 0, 1, 0, 1));
+    fn empty() -> Index<'a, T> {
+        Index {
+            sequence: &[],
+            elements: HashMap::new(),
+            junk: HashSet::new(),
+            popular: HashSet::new(),
+            autojunk: false,
         }
+    }
+
+    pub fn sequence(&self) -> &'a [T] {
+        self.sequence
+    }
+
+    pub fn junk(&self) -> &HashSet<&'a T> {
+        &self.junk
+    }
 
-        if codes.first().unwrap().tag == "equal" {
-            let opcode = codes.first_mut().unwrap();
-            opcode.first_start = max(opcode.first_start, opcode.first_end.saturating_sub(n));
-            opcode.second_start = max(opcode.second_start, opcode.second_end.saturating_sub(n));
+    pub fn popular(&self) -> &HashSet<&'a T> {
+        &self.popular
+    }
+}
+
+// An `Index` kept together with the sequence it was built for. The index
+// borrows from the allocation behind `sequence`, which is never mutated and
+// stays in place for as long as this `Arc` or a clone of it is alive, so the
+// index is only handed out for the lifetime of `&self`.
+struct OwnedIndex<'a, T: 'a + Sequence> {
+    // Declared first so that it is dropped before the sequence.
+    index: Index<'a, T>,
+    sequence: Arc<[T]>,
+}
+
+impl<'a, T: Sequence> OwnedIndex<'a, T> {
+    fn new<F>(sequence: Arc<[T]>, is_junk: Option<F>, autojunk: bool) -> OwnedIndex<'a, T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        // SAFETY: see the comment on the struct.
+        let borrowed: &'a [T] = unsafe { &*(&*sequence as *const [T]) };
+        OwnedIndex {
+            index: Index::with_junk(borrowed, is_junk, autojunk),
+            sequence,
         }
-        if codes.last().unwrap().tag == "equal" {
-            let opcode = codes.last_mut().unwrap();
-            opcode.first_end = min(opcode.first_start + n, opcode.first_end);
-            opcode.second_end = min(opcode.second_start + n, opcode.second_end);
+    }
+
+    fn index(&self) -> &Index<'_, T> {
+        &self.index
+    }
+
+    fn sequence(&self) -> &Arc<[T]> {
+        &self.sequence
+    }
+}
+
+impl<'a, T: Sequence> Clone for OwnedIndex<'a, T> {
+    fn clone(&self) -> OwnedIndex<'a, T> {
+        // The cloned `Arc` shares the allocation the index borrows from.
+        OwnedIndex {
+            index: self.index.clone(),
+            sequence: self.sequence.clone(),
         }
-        let nn = n + n;
-        let mut group = Vec::new();
-        for code in &codes {
-            let (mut first_start, mut second_start) = (code.first_start, code.second_start);
-            if code.tag == "equal" && code.first_end - code.first_start > nn {
-                group.push(Opcode::new(
-                    code.tag.clone(),
-                    code.first_start,
-                    min(code.first_end, code.first_start + n),
-                    code.second_start,
-                    min(code.second_end, code.second_start + n),
-                ));
-                res.push(group.clone());
-                group.clear();
-                first_start = max(first_start, code.first_end.saturating_sub(n));
-                second_start = max(second_start, code.second_end.saturating_sub(n));
-            }
-            group.push(Opcode::new(
-                code.tag.clone(),
-                first_start,
-                code.first_end,
-                second_start,
-                code.second_end,
+    }
+}
+
+fn opcodes_from_blocks(matching_blocks: &[Match]) -> Vec<Opcode> {
+    let mut opcodes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for m in matching_blocks {
+        let tag = if i < m.first_start && j < m.second_start {
+            Some(Tag::Replace)
+        } else if i < m.first_start {
+            Some(Tag::Delete)
+        } else if j < m.second_start {
+            Some(Tag::Insert)
+        } else {
+            None
+        };
+        if let Some(tag) = tag {
+            opcodes.push(Opcode::new(tag, i, m.first_start, j, m.second_start));
+        }
+        i = m.first_start + m.size;
+        j = m.second_start + m.size;
+        if m.size != 0 {
+            opcodes.push(Opcode::new(
+                Tag::Equal,
+                m.first_start,
+                i,
+                m.second_start,
+                j,
             ));
         }
-        if !(group.len() == 1 && group.first().unwrap().tag == "equal") || group.is_empty() {
+    }
+    opcodes
+}
+
+// Ratio of the elements in `matching_blocks` to the elements of both
+// sequences.
+fn matching_ratio(matching_blocks: &[Match], first_length: usize, second_length: usize) -> f32 {
+    let matches = matching_blocks.iter().fold(0, |res, m| res + m.size);
+    calculate_ratio(matches, first_length + second_length)
+}
+
+// Upper bound on the ratio given only the lengths of the sequences.
+fn length_ratio(first_length: usize, second_length: usize) -> f32 {
+    calculate_ratio(
+        min(first_length, second_length),
+        first_length + second_length,
+    )
+}
+
+fn group_opcodes(mut codes: Vec<Opcode>, n: usize) -> Vec<Vec<Opcode>> {
+    let mut res = Vec::new();
+    if codes.is_empty() {
+        codes.push(Opcode::new(Tag::Equal, 0, 1, 0, 1));
+    }
+
+    if codes.first().unwrap().tag == Tag::Equal {
+        let opcode = codes.first_mut().unwrap();
+        opcode.first_start = max(opcode.first_start, opcode.first_end.saturating_sub(n));
+        opcode.second_start = max(opcode.second_start, opcode.second_end.saturating_sub(n));
+    }
+    if codes.last().unwrap().tag == Tag::Equal {
+        let opcode = codes.last_mut().unwrap();
+        opcode.first_end = min(opcode.first_start + n, opcode.first_end);
+        opcode.second_end = min(opcode.second_start + n, opcode.second_end);
+    }
+    let nn = n + n;
+    let mut group = Vec::new();
+    for code in &codes {
+        let (mut first_start, mut second_start) = (code.first_start, code.second_start);
+        if code.tag == Tag::Equal && code.first_end - code.first_start > nn {
+            group.push(Opcode::new(
+                code.tag,
+                code.first_start,
+                min(code.first_end, code.first_start + n),
+                code.second_start,
+                min(code.second_end, code.second_start + n),
+            ));
             res.push(group.clone());
+            group.clear();
+            first_start = max(first_start, code.first_end.saturating_sub(n));
+            second_start = max(second_start, code.second_end.saturating_sub(n));
+        }
+        group.push(Opcode::new(
+            code.tag,
+            first_start,
+            code.first_end,
+            second_start,
+            code.second_end,
+        ));
+    }
+    if !(group.len() == 1 && group.first().unwrap().tag == Tag::Equal) || group.is_empty() {
+        res.push(group.clone());
+    }
+    res
+}
+
+/// Compares sequences by a key taken from each element, such as one field
+/// of a struct. Opcodes and matching blocks index into the original slices.
+/// The index of the second sequence's keys is built once, like in
+/// `OwnedSequenceMatcher`.
+pub struct KeyedSequenceMatcher<'a, T: 'a, K: 'a + Sequence> {
+    first_sequence: &'a [T],
+    second_sequence: &'a [T],
+    first_keys: Vec<K>,
+    second_index: OwnedIndex<'a, K>,
+    is_junk: Option<JunkFn<'a, K>>,
+    autojunk: bool,
+    algorithm: Algorithm,
+    matching_blocks: Option<Vec<Match>>,
+    opcodes: Option<Vec<Opcode>>,
+}
+
+impl<'a, T, K: Sequence> KeyedSequenceMatcher<'a, T, K> {
+    pub fn new<F>(
+        first_sequence: &'a [T],
+        second_sequence: &'a [T],
+        key: F,
+    ) -> KeyedSequenceMatcher<'a, T, K>
+    where
+        F: Fn(&T) -> K,
+    {
+        KeyedSequenceMatcher::with_autojunk(first_sequence, second_sequence, key, true)
+    }
+
+    pub fn with_autojunk<F>(
+        first_sequence: &'a [T],
+        second_sequence: &'a [T],
+        key: F,
+        autojunk: bool,
+    ) -> KeyedSequenceMatcher<'a, T, K>
+    where
+        F: Fn(&T) -> K,
+    {
+        KeyedSequenceMatcher {
+            first_sequence,
+            second_sequence,
+            first_keys: first_sequence.iter().map(&key).collect(),
+            second_index: OwnedIndex::new(
+                second_sequence.iter().map(&key).collect(),
+                None::<fn(&K) -> bool>,
+                autojunk,
+            ),
+            is_junk: None,
+            autojunk,
+            algorithm: Algorithm::default(),
+            matching_blocks: None,
+            opcodes: None,
+        }
+    }
+
+    /// Junk is decided on the keys.
+    pub fn set_is_junk<F>(&mut self, is_junk: Option<F>)
+    where
+        F: Fn(&K) -> bool + Send + Sync + 'a,
+    {
+        self.is_junk = is_junk.map(|f| Box::new(f) as JunkFn<'a, K>);
+        self.matching_blocks = None;
+        self.opcodes = None;
+        self.second_index = OwnedIndex::new(
+            self.second_index.sequence().clone(),
+            self.is_junk.as_ref(),
+            self.autojunk,
+        );
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        self.algorithm = algorithm;
+        self.matching_blocks = None;
+        self.opcodes = None;
+    }
+
+    pub fn first_sequence(&self) -> &'a [T] {
+        self.first_sequence
+    }
+
+    pub fn second_sequence(&self) -> &'a [T] {
+        self.second_sequence
+    }
+
+    pub fn first_keys(&self) -> &[K] {
+        &self.first_keys
+    }
+
+    pub fn second_keys(&self) -> &[K] {
+        self.second_index.sequence()
+    }
+
+    // Matcher over the keys, sharing the index of the second ones.
+    fn matcher(&self) -> SequenceMatcher<'_, K> {
+        let mut matcher = SequenceMatcher::with_index(&self.first_keys, self.second_index.index());
+        matcher.set_algorithm(self.algorithm);
+        matcher
+    }
+
+    pub fn get_matching_blocks(&mut self) -> Vec<Match> {
+        if self.matching_blocks.as_ref().is_none() {
+            let matching_blocks = self.matcher().get_matching_blocks();
+            self.matching_blocks = Some(matching_blocks);
+        }
+        self.matching_blocks.as_ref().unwrap().clone()
+    }
+
+    pub fn get_opcodes(&mut self) -> Vec<Opcode> {
+        if self.opcodes.as_ref().is_none() {
+            self.opcodes = Some(opcodes_from_blocks(&self.get_matching_blocks()));
         }
-        res
+        self.opcodes.as_ref().unwrap().clone()
+    }
+
+    pub fn get_grouped_opcodes(&mut self, n: usize) -> Vec<Vec<Opcode>> {
+        group_opcodes(self.get_opcodes(), n)
     }
 
     pub fn ratio(&mut self) -> f32 {
-        let matches = self.get_matching_blocks()
-            .iter()
-            .fold(0, |res, &m| res + m.size);
-        calculate_ratio(
-            matches,
-            self.first_sequence.len() + self.second_sequence.len(),
+        let matching_blocks = self.get_matching_blocks();
+        matching_ratio(
+            &matching_blocks,
+            self.first_keys.len(),
+            self.second_keys().len(),
+        )
+    }
+
+    pub fn quick_ratio(&self) -> f32 {
+        self.matcher().quick_ratio()
+    }
+
+    pub fn real_quick_ratio(&self) -> f32 {
+        length_ratio(self.first_keys.len(), self.second_keys().len())
+    }
+}
+
+/// A `SequenceMatcher` that owns its sequences, so that it can be stored
+/// alongside other data or moved to another thread. It is `Send` and `Sync`
+/// when `T` is, and `T` can't hold borrowed data. The index of the second
+/// sequence is built once, computed matching blocks and opcodes are kept
+/// until the sequences or settings change.
+pub struct OwnedSequenceMatcher<T: 'static + Sequence> {
+    first_sequence: Arc<[T]>,
+    second_index: OwnedIndex<'static, T>,
+    is_junk: Option<SharedJunkFn<T>>,
+    autojunk: bool,
+    algorithm: Algorithm,
+    matching_blocks: Option<Vec<Match>>,
+    opcodes: Option<Vec<Opcode>>,
+}
+
+impl<T: 'static + Sequence> Clone for OwnedSequenceMatcher<T> {
+    fn clone(&self) -> OwnedSequenceMatcher<T> {
+        OwnedSequenceMatcher {
+            first_sequence: self.first_sequence.clone(),
+            second_index: self.second_index.clone(),
+            is_junk: self.is_junk.clone(),
+            autojunk: self.autojunk,
+            algorithm: self.algorithm,
+            matching_blocks: self.matching_blocks.clone(),
+            opcodes: self.opcodes.clone(),
+        }
+    }
+}
+
+impl<T: 'static + Sequence> OwnedSequenceMatcher<T> {
+    /// Takes anything that converts into `Arc<[T]>`, such as a `Vec<T>` or an
+    /// `Arc<[T]>` shared with other owners.
+    pub fn new<S>(first_sequence: S, second_sequence: S) -> OwnedSequenceMatcher<T>
+    where
+        S: Into<Arc<[T]>>,
+    {
+        OwnedSequenceMatcher::with_autojunk(first_sequence, second_sequence, true)
+    }
+
+    pub fn with_autojunk<S>(
+        first_sequence: S,
+        second_sequence: S,
+        autojunk: bool,
+    ) -> OwnedSequenceMatcher<T>
+    where
+        S: Into<Arc<[T]>>,
+    {
+        OwnedSequenceMatcher {
+            first_sequence: first_sequence.into(),
+            second_index: OwnedIndex::new(
+                second_sequence.into(),
+                None::<fn(&T) -> bool>,
+                autojunk,
+            ),
+            is_junk: None,
+            autojunk,
+            algorithm: Algorithm::default(),
+            matching_blocks: None,
+            opcodes: None,
+        }
+    }
+
+    pub fn set_is_junk<F>(&mut self, is_junk: Option<F>)
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.is_junk = is_junk.map(|f| Arc::new(f) as SharedJunkFn<T>);
+        self.matching_blocks = None;
+        self.opcodes = None;
+        self.chain_second_seq(self.second_index.sequence().clone());
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        self.algorithm = algorithm;
+        self.matching_blocks = None;
+        self.opcodes = None;
+    }
+
+    pub fn set_seqs<S>(&mut self, first_sequence: S, second_sequence: S)
+    where
+        S: Into<Arc<[T]>>,
+    {
+        self.set_first_seq(first_sequence);
+        self.set_second_seq(second_sequence);
+    }
+
+    pub fn set_first_seq<S: Into<Arc<[T]>>>(&mut self, sequence: S) {
+        self.first_sequence = sequence.into();
+        self.matching_blocks = None;
+        self.opcodes = None;
+    }
+
+    pub fn set_second_seq<S: Into<Arc<[T]>>>(&mut self, sequence: S) {
+        self.matching_blocks = None;
+        self.opcodes = None;
+        self.chain_second_seq(sequence.into());
+    }
+
+    fn chain_second_seq(&mut self, sequence: Arc<[T]>) {
+        let is_junk = self
+            .is_junk
+            .as_ref()
+            .map(|is_junk| move |item: &T| is_junk(item));
+        self.second_index = OwnedIndex::new(sequence, is_junk, self.autojunk);
+    }
+
+    pub fn first_sequence(&self) -> &Arc<[T]> {
+        &self.first_sequence
+    }
+
+    pub fn second_sequence(&self) -> &Arc<[T]> {
+        self.second_index.sequence()
+    }
+
+    /// A matcher borrowing the sequences and the index of the second one, for
+    /// the methods not offered here.
+    pub fn matcher(&self) -> SequenceMatcher<'_, T> {
+        let mut matcher =
+            SequenceMatcher::with_index(&*self.first_sequence, self.second_index.index());
+        matcher.set_algorithm(self.algorithm);
+        matcher
+    }
+
+    pub fn get_matching_blocks(&mut self) -> Vec<Match> {
+        if self.matching_blocks.as_ref().is_none() {
+            let matching_blocks = self.matcher().get_matching_blocks();
+            self.matching_blocks = Some(matching_blocks);
+        }
+        self.matching_blocks.as_ref().unwrap().clone()
+    }
+
+    pub fn get_opcodes(&mut self) -> Vec<Opcode> {
+        if self.opcodes.as_ref().is_none() {
+            self.opcodes = Some(opcodes_from_blocks(&self.get_matching_blocks()));
+        }
+        self.opcodes.as_ref().unwrap().clone()
+    }
+
+    pub fn get_grouped_opcodes(&mut self, n: usize) -> Vec<Vec<Opcode>> {
+        group_opcodes(self.get_opcodes(), n)
+    }
+
+    pub fn ratio(&mut self) -> f32 {
+        let matching_blocks = self.get_matching_blocks();
+        matching_ratio(
+            &matching_blocks,
+            self.first_sequence.len(),
+            self.second_index.sequence().len(),
+        )
+    }
+
+    pub fn quick_ratio(&self) -> f32 {
+        self.matcher().quick_ratio()
+    }
+
+    pub fn real_quick_ratio(&self) -> f32 {
+        length_ratio(
+            self.first_sequence.len(),
+            self.second_index.sequence().len(),
         )
     }
 }
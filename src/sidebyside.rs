@@ -0,0 +1,206 @@
+use differ::{Differ, LineOp};
+
+const GUTTER_WIDTH_MINIMUM: usize = 3;
+
+/// Two-column output in the style of `diff -y`.
+pub struct SideBySideDiff {
+    /// Total width of an output line. When it is too narrow for any text
+    /// only the markers are written.
+    pub width: usize,
+    /// Columns between tab stops, a size of 0 is treated as 1.
+    pub tab_size: usize,
+    /// Write spaces instead of tabs, both within lines and for padding.
+    pub expand_tabs: bool,
+    /// Leave out lines that are the same on both sides.
+    pub suppress_common_lines: bool,
+    /// Write only the left side of common lines, marked with `(`.
+    pub left_column: bool,
+    /// Pairs up changed lines, similar lines end up on the same row.
+    pub differ: Differ,
+}
+
+impl Default for SideBySideDiff {
+    fn default() -> SideBySideDiff {
+        SideBySideDiff::new()
+    }
+}
+
+impl SideBySideDiff {
+    pub fn new() -> SideBySideDiff {
+        SideBySideDiff {
+            width: 130,
+            tab_size: 8,
+            expand_tabs: false,
+            suppress_common_lines: false,
+            left_column: false,
+            differ: Differ::new(),
+        }
+    }
+
+    /// Returns the rows of the two-column view. Changed lines are marked with
+    /// `|`, lines only on the left with `<` and lines only on the right with `>`.
+    pub fn make_lines(&self, from_lines: &[&str], to_lines: &[&str]) -> Vec<String> {
+        let mut res = Vec::new();
+        let ops = self.differ.line_ops(from_lines, to_lines);
+        let mut i = 0;
+        while i < ops.len() {
+            match ops[i] {
+                LineOp::Equal(first, second) => {
+                    if !self.suppress_common_lines {
+                        res.push(if self.left_column {
+                            self.row(Some(from_lines[first]), '(', None)
+                        } else {
+                            self.row(Some(from_lines[first]), ' ', Some(to_lines[second]))
+                        });
+                    }
+                    i += 1;
+                }
+                LineOp::Replace(first, second) => {
+                    res.push(self.row(Some(from_lines[first]), '|', Some(to_lines[second])));
+                    i += 1;
+                }
+                LineOp::Delete(_) | LineOp::Insert(_) => {
+                    // Lines the differ found nothing similar for are paired
+                    // by position, like diff does.
+                    let (mut deleted, mut inserted) = (Vec::new(), Vec::new());
+                    while i < ops.len() {
+                        match ops[i] {
+                            LineOp::Delete(first) => deleted.push(from_lines[first]),
+                            LineOp::Insert(second) => inserted.push(to_lines[second]),
+                            _ => break,
+                        }
+                        i += 1;
+                    }
+                    for k in 0..deleted.len().max(inserted.len()) {
+                        let (left, right) = (deleted.get(k).cloned(), inserted.get(k).cloned());
+                        let separator = match (left, right) {
+                            (Some(_), Some(_)) => '|',
+                            (Some(_), None) => '<',
+                            _ => '>',
+                        };
+                        res.push(self.row(left, separator, right));
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    fn tab_size(&self) -> usize {
+        self.tab_size.max(1)
+    }
+
+    // Width of each half and the column the right half starts at, computed
+    // the same way as diff so that tab stops line up.
+    fn columns(&self) -> (usize, usize) {
+        let tab = if self.expand_tabs { 1 } else { self.tab_size() };
+        let offset = (self.width + tab + GUTTER_WIDTH_MINIMUM) / (2 * tab) * tab;
+        let half_width = offset
+            .saturating_sub(GUTTER_WIDTH_MINIMUM)
+            .min(self.width.saturating_sub(offset));
+        let column2_offset = if half_width > 0 { offset } else { self.width };
+        (half_width, column2_offset)
+    }
+
+    fn row(&self, left: Option<&str>, separator: char, right: Option<&str>) -> String {
+        let (half_width, column2_offset) = self.columns();
+        let mut res = String::new();
+        let mut column = 0;
+        let mut put_newline = false;
+        if let Some(left) = left {
+            put_newline |= left.ends_with('\n');
+            column = self.half_line(&mut res, left, 0, half_width);
+        }
+        if separator != ' ' {
+            column = self.pad(&mut res, column, (half_width + column2_offset).saturating_sub(1) / 2) + 1;
+            // A changed line that differs only by its trailing newline.
+            let separator = match right {
+                Some(right) if separator == '|' && put_newline != right.ends_with('\n') => {
+                    if put_newline {
+                        '/'
+                    } else {
+                        '\\'
+                    }
+                }
+                _ => separator,
+            };
+            res.push(separator);
+        }
+        if let Some(right) = right {
+            put_newline |= right.ends_with('\n');
+            if !right.starts_with('\n') {
+                column = self.pad(&mut res, column, column2_offset);
+                self.half_line(&mut res, right, column, half_width);
+            }
+        }
+        if put_newline {
+            res.push('\n');
+        }
+        res
+    }
+
+    // Pads from column `from` to column `to`, with tabs where possible.
+    fn pad(&self, res: &mut String, mut from: usize, to: usize) -> usize {
+        if !self.expand_tabs {
+            let tab_size = self.tab_size();
+            let mut tab = from + tab_size - from % tab_size;
+            while tab <= to {
+                res.push('\t');
+                from = tab;
+                tab += tab_size;
+            }
+        }
+        while from < to {
+            res.push(' ');
+            from += 1;
+        }
+        to
+    }
+
+    // Writes as much of `line` as fits in `bound` columns and returns the
+    // column reached.
+    fn half_line(&self, res: &mut String, line: &str, indent: usize, bound: usize) -> usize {
+        let tab_size = self.tab_size();
+        let (mut in_position, mut out_position) = (0, 0);
+        for c in line.chars() {
+            match c {
+                '\t' => {
+                    let spaces = tab_size - in_position % tab_size;
+                    if in_position == out_position {
+                        let tabstop = out_position + spaces;
+                        if self.expand_tabs {
+                            while out_position < tabstop.min(bound) {
+                                res.push(' ');
+                                out_position += 1;
+                            }
+                        } else if tabstop < bound {
+                            out_position = tabstop;
+                            res.push(c);
+                        }
+                    }
+                    in_position += spaces;
+                }
+                '\r' => {
+                    res.push(c);
+                    self.pad(res, 0, indent);
+                    in_position = 0;
+                    out_position = 0;
+                }
+                '\n' => return out_position,
+                c if c.is_control() => {
+                    if in_position < bound {
+                        res.push(c);
+                    }
+                }
+                _ => {
+                    if in_position < bound {
+                        out_position = in_position + 1;
+                        res.push(c);
+                    }
+                    in_position += 1;
+                }
+            }
+        }
+        out_position
+    }
+}
@@ -13,13 +13,28 @@ pub fn str_with_similar_chars(c: char, length: usize) -> String {
     s
 }
 
-pub fn count_leading(line: &str, c: char) -> usize {
-    let (mut i, n) = (0, line.len());
-    let line: Vec<char> = line.chars().collect();
-    while (i < n) && line[i] == c {
-        i += 1;
-    }
-    i
+pub fn keep_original_whitespace(line: &str, tags: &str) -> String {
+    line.chars()
+        .zip(tags.chars())
+        .map(|(c, tag)| if tag == ' ' && c.is_whitespace() { c } else { tag })
+        .collect()
+}
+
+pub fn common_prefix_len<T: PartialEq>(first: &[T], second: &[T]) -> usize {
+    first
+        .iter()
+        .zip(second.iter())
+        .take_while(|&(a, b)| a == b)
+        .count()
+}
+
+pub fn common_suffix_len<T: PartialEq>(first: &[T], second: &[T]) -> usize {
+    first
+        .iter()
+        .rev()
+        .zip(second.iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count()
 }
 
 pub fn format_range_unified(start: usize, end: usize) -> String {
@@ -45,3 +60,35 @@ pub fn format_range_context(start: usize, end: usize) -> String {
     }
     format!("{},{}", beginning, beginning + length - 1)
 }
+
+pub fn format_range_normal(start: usize, end: usize) -> String {
+    if end - start <= 1 {
+        return end.to_string();
+    }
+    format!("{},{}", start + 1, end)
+}
+
+pub fn expand_tabs(line: &str, tab_size: usize) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                if tab_size > 0 {
+                    let spaces = tab_size - column % tab_size;
+                    res.push_str(&str_with_similar_chars(' ', spaces));
+                    column += spaces;
+                }
+            }
+            '\n' | '\r' => {
+                res.push(c);
+                column = 0;
+            }
+            _ => {
+                res.push(c);
+                column += 1;
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,245 @@
+use color::Theme;
+#[cfg(feature = "regex")]
+use regex::Regex;
+use sequencematcher::{Opcode, SequenceMatcher, Tag};
+use unicode_segmentation::UnicodeSegmentation;
+use utils::format_range_unified;
+use DiffOptions;
+
+const RESET: &str = "\x1b[0m";
+
+pub type TokenizeFn = Box<dyn Fn(&str) -> Vec<&str>>;
+
+/// How lines are split into the words that are compared.
+pub enum Tokenizer {
+    /// Runs of non-whitespace characters, like `git diff --word-diff`.
+    Whitespace,
+    /// Pieces between the word boundaries of Unicode Standard Annex #29, so
+    /// that words, runs of white space and punctuation marks are separate.
+    UnicodeWords,
+    /// Every match of the regex is a word, like `--word-diff-regex`. Only
+    /// with the `regex` feature.
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+    /// Splits a line, given without its newline, into pieces that join back
+    /// to it.
+    Custom(TokenizeFn),
+}
+
+impl Tokenizer {
+    /// Splits `text` into words and the text between them, so that the
+    /// pieces join back to `text`. Newlines are always pieces of their own.
+    pub fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let mut res = Vec::new();
+        for line in text.split_inclusive('\n') {
+            let body = line.strip_suffix('\n').unwrap_or(line);
+            match *self {
+                Tokenizer::Whitespace => {
+                    split_runs(&mut res, body, |c| if c.is_whitespace() { 1 } else { 2 })
+                }
+                Tokenizer::UnicodeWords => res.extend(body.split_word_bounds()),
+                #[cfg(feature = "regex")]
+                Tokenizer::Regex(ref regex) => {
+                    let mut end = 0;
+                    for word in regex.find_iter(body).filter(|word| !word.is_empty()) {
+                        if end < word.start() {
+                            res.push(&body[end..word.start()]);
+                        }
+                        res.push(word.as_str());
+                        end = word.end();
+                    }
+                    if end < body.len() {
+                        res.push(&body[end..]);
+                    }
+                }
+                Tokenizer::Custom(ref tokenize) => {
+                    res.extend(tokenize(body).into_iter().filter(|word| !word.is_empty()))
+                }
+            }
+            if body.len() < line.len() {
+                res.push("\n");
+            }
+        }
+        res
+    }
+}
+
+// Pushes runs of chars of the same class.
+fn split_runs<'a, F: Fn(char) -> u8>(res: &mut Vec<&'a str>, text: &'a str, class: F) {
+    let mut start = 0;
+    let mut previous = None;
+    for (offset, c) in text.char_indices() {
+        let current = class(c);
+        if offset > 0 && previous != Some(current) {
+            res.push(&text[start..offset]);
+            start = offset;
+        }
+        previous = Some(current);
+    }
+    if start < text.len() {
+        res.push(&text[start..]);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WordDiffMode {
+    /// Removed words in `[-` `-]` and added words in `{+` `+}`.
+    #[default]
+    Plain,
+    /// One line per piece of text prefixed with ` `, `-` or `+`, and a `~`
+    /// line for each newline, as `git diff --word-diff=porcelain` writes.
+    Porcelain,
+    /// Removed and added words coloured with `WordDiff::theme`.
+    Color,
+}
+
+/// Word-level diffs in the style of `git diff --word-diff`.
+pub struct WordDiff {
+    pub tokenizer: Tokenizer,
+    pub mode: WordDiffMode,
+    /// Colours of `WordDiffMode::Color`, `removed` and `added` are used for
+    /// the changed words.
+    pub theme: Theme,
+    /// Context size and algorithm used to find the changed lines, and the
+    /// terminator of hunk headers.
+    pub options: DiffOptions,
+}
+
+impl Default for WordDiff {
+    fn default() -> WordDiff {
+        WordDiff::new()
+    }
+}
+
+impl WordDiff {
+    pub fn new() -> WordDiff {
+        WordDiff {
+            tokenizer: Tokenizer::Whitespace,
+            mode: WordDiffMode::default(),
+            theme: Theme::new(),
+            options: DiffOptions::new(),
+        }
+    }
+
+    /// Finds the changed lines like `unified_diff` does and writes each hunk
+    /// with a `@@` header, followed by its lines with changed words marked.
+    pub fn make_lines(&self, from_lines: &[&str], to_lines: &[&str]) -> Vec<String> {
+        let mut res = Vec::new();
+        for group in self.options.grouped_opcodes(from_lines, to_lines) {
+            let (first, last) = (group[0], group[group.len() - 1]);
+            let header = format!(
+                "@@ -{} +{} @@{}",
+                format_range_unified(first.first_start, last.first_end),
+                format_range_unified(first.second_start, last.second_end),
+                self.options.lineterm
+            );
+            res.push(self.paint(&self.theme.hunk_header, header));
+            let mut body = String::new();
+            for opcode in group {
+                let from_text = from_lines[opcode.first_start..opcode.first_end].concat();
+                let to_text = to_lines[opcode.second_start..opcode.second_end].concat();
+                match opcode.tag {
+                    Tag::Equal => self.push_text(&mut body, Tag::Equal, &from_text),
+                    Tag::Delete => self.push_text(&mut body, Tag::Delete, &from_text),
+                    Tag::Insert => self.push_text(&mut body, Tag::Insert, &to_text),
+                    Tag::Replace => self.push_words(&mut body, &from_text, &to_text),
+                }
+            }
+            res.extend(body.split_inclusive('\n').map(|line| line.to_string()));
+        }
+        res
+    }
+
+    /// Marks the changed words of two texts.
+    pub fn diff_text(&self, from_text: &str, to_text: &str) -> String {
+        let mut res = String::new();
+        self.push_words(&mut res, from_text, to_text);
+        res
+    }
+
+    fn push_words(&self, res: &mut String, from_text: &str, to_text: &str) {
+        let from_words = self.tokenizer.tokenize(from_text);
+        let to_words = self.tokenizer.tokenize(to_text);
+        let mut matcher = SequenceMatcher::with_autojunk(&from_words, &to_words, false);
+        matcher.set_algorithm(self.options.algorithm);
+        for Opcode {
+            tag,
+            first_start,
+            first_end,
+            second_start,
+            second_end,
+        } in matcher.get_opcodes()
+        {
+            let from_part = from_words[first_start..first_end].concat();
+            let to_part = to_words[second_start..second_end].concat();
+            match tag {
+                Tag::Equal => self.push_text(res, Tag::Equal, &from_part),
+                Tag::Delete => self.push_text(res, Tag::Delete, &from_part),
+                Tag::Insert => self.push_text(res, Tag::Insert, &to_part),
+                Tag::Replace => {
+                    self.push_text(res, Tag::Delete, &from_part);
+                    self.push_text(res, Tag::Insert, &to_part);
+                }
+            }
+        }
+    }
+
+    // Writes text of one kind, marking each piece between newlines on its
+    // own so that markers never span lines.
+    fn push_text(&self, res: &mut String, tag: Tag, text: &str) {
+        for line in text.split_inclusive('\n') {
+            let (body, newline) = match line.strip_suffix('\n') {
+                Some(body) => (body, true),
+                None => (line, false),
+            };
+            if !body.is_empty() {
+                match self.mode {
+                    WordDiffMode::Plain => match tag {
+                        Tag::Delete => res.push_str(&format!("[-{}-]", body)),
+                        Tag::Insert => res.push_str(&format!("{{+{}+}}", body)),
+                        _ => res.push_str(body),
+                    },
+                    WordDiffMode::Porcelain => {
+                        res.push(match tag {
+                            Tag::Delete => '-',
+                            Tag::Insert => '+',
+                            _ => ' ',
+                        });
+                        res.push_str(body);
+                        res.push('\n');
+                    }
+                    WordDiffMode::Color => {
+                        let style = match tag {
+                            Tag::Delete => &self.theme.removed,
+                            Tag::Insert => &self.theme.added,
+                            _ => &self.theme.context,
+                        };
+                        if style.is_empty() {
+                            res.push_str(body);
+                        } else {
+                            res.push_str(style);
+                            res.push_str(body);
+                            res.push_str(RESET);
+                        }
+                    }
+                }
+            }
+            if newline {
+                match self.mode {
+                    WordDiffMode::Porcelain => res.push_str("~\n"),
+                    _ => res.push('\n'),
+                }
+            }
+        }
+    }
+
+    fn paint(&self, style: &str, line: String) -> String {
+        if self.mode != WordDiffMode::Color || style.is_empty() {
+            return line;
+        }
+        match line.strip_suffix('\n') {
+            Some(body) => format!("{}{}{}\n", style, body, RESET),
+            None => format!("{}{}{}", style, line, RESET),
+        }
+    }
+}
@@ -1,7 +1,25 @@
 extern crate difflib;
+#[cfg(feature = "regex")]
+extern crate regex;
 
-use difflib::differ::Differ;
-use difflib::sequencematcher::{Match, Opcode, SequenceMatcher};
+use difflib::color::{ColorDiff, Theme};
+use difflib::compare::CompareOptions;
+use difflib::differ::{Differ, IntralineDiff};
+use difflib::git::{GitFile, GitOptions, EXECUTABLE_FILE_MODE};
+use difflib::html::HtmlDiff;
+use difflib::merge::{ConflictStyle, MergeOptions};
+use difflib::patch::{ApplyOptions, Format, HunkResult, LineKind, Patch};
+use difflib::sequencematcher::{
+    Algorithm, Index, KeyedSequenceMatcher, Match, Opcode, OwnedSequenceMatcher, SequenceMatcher,
+    Tag,
+};
+use difflib::sidebyside::SideBySideDiff;
+use difflib::worddiff::{Tokenizer, WordDiff, WordDiffMode};
+#[cfg(feature = "regex")]
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::thread;
 
 #[test]
 fn test_longest_match() {
@@ -41,35 +59,35 @@ fn test_get_opcodes() {
     let result = matcher.get_opcodes();
     let mut expected_result = Vec::new();
     expected_result.push(Opcode {
-        tag: "delete".to_string(),
+        tag: Tag::Delete,
         first_start: 0,
         first_end: 1,
         second_start: 0,
         second_end: 0,
     });
     expected_result.push(Opcode {
-        tag: "equal".to_string(),
+        tag: Tag::Equal,
         first_start: 1,
         first_end: 3,
         second_start: 0,
         second_end: 2,
     });
     expected_result.push(Opcode {
-        tag: "replace".to_string(),
+        tag: Tag::Replace,
         first_start: 3,
         first_end: 4,
         second_start: 2,
         second_end: 3,
     });
     expected_result.push(Opcode {
-        tag: "equal".to_string(),
+        tag: Tag::Equal,
         first_start: 4,
         first_end: 6,
         second_start: 3,
         second_end: 5,
     });
     expected_result.push(Opcode {
-        tag: "insert".to_string(),
+        tag: Tag::Insert,
         first_start: 6,
         first_end: 6,
         second_start: 5,
@@ -115,7 +133,7 @@ fn test_differ_compare_with_func() {
     let first_text = vec!["one\n", "two\n", "three\n"];
     let second_text = vec!["ore\n", "tree\n", "emu\n"];
     let mut differ = Differ::new();
-    differ.char_junk = Some(is_junk_char);
+    differ.char_junk = Some(Box::new(is_junk_char));
     let result = differ.compare(&first_text, &second_text).join("");
     assert_eq!(
         result,
@@ -123,6 +141,20 @@ fn test_differ_compare_with_func() {
     );
 }
 
+#[test]
+fn test_differ_compare_replace_at_different_offsets() {
+    // The similar line is found even when the replaced ranges start at
+    // different positions in the two sequences.
+    let first_text = vec!["x\n", "abcdefgh\n"];
+    let second_text = vec!["abcdefgX\n"];
+    let differ = Differ::new();
+    let result = differ.compare(&first_text, &second_text).join("");
+    assert_eq!(
+        result,
+        "- x\n- abcdefgh\n?        ^\n+ abcdefgX\n?        ^\n"
+    );
+}
+
 #[test]
 fn test_differ_restore() {
     let first_text = vec!["one\n", "  two\n", "three\n"];
@@ -149,7 +181,7 @@ fn test_unified_diff() {
     assert_eq!(
         result,
         "--- Original\t2005-01-26 23:30:50\n+++ Current\t2010-04-02 10:20:52\n@@ -1,4 \
-         +1,4 @@\n+zero one-two-three+tree four"
+         +1,4 @@\n+zero one-two-three+tree four\n\\ No newline at end of file\n"
     );
 }
 
@@ -169,8 +201,8 @@ fn test_context_diff() {
     assert_eq!(
         result,
         "*** Original\t2005-01-26 23:30:50\n--- Current\t2010-04-02 \
-         10:20:52\n***************\n*** 1,4 ****\n  one! two! three  four--- 1,4 ----\n+ \
-         zero  one! tree  four"
+         10:20:52\n***************\n*** 1,4 ****\n  one! two! three  four\n\\ No newline at \
+         end of file\n--- 1,4 ----\n+ zero  one! tree  four\n\\ No newline at end of file\n"
     );
 }
 
@@ -192,3 +224,1362 @@ fn test_integer_slice() {
     });
     assert_eq!(result, expected_result);
 }
+
+#[test]
+fn test_html_diff_make_table() {
+    let first_text = vec!["one\n", "two\n", "three\n"];
+    let second_text = vec!["ore\n", "two\n", "tree\n", "emu\n"];
+    let result = HtmlDiff::new().make_table(&first_text, &second_text, "", "", false, 5);
+    assert!(result.contains(
+        "<td nowrap=\"nowrap\">t<span class=\"diff_sub\">h</span>ree</td>"
+    ));
+    assert!(result.contains(
+        "<td class=\"diff_header\"></td><td nowrap=\"nowrap\"></td><td class=\"diff_next\"></td>"
+    ));
+    assert!(result.contains("<td nowrap=\"nowrap\"><span class=\"diff_add\">emu</span></td>"));
+    assert_eq!(result.matches("<tr>").count(), 4);
+}
+
+#[test]
+fn test_html_diff_no_differences() {
+    let text = vec!["one\n", "two\n"];
+    let result = HtmlDiff::new().make_file(&text, &text, "a", "b", true, 5);
+    assert!(result.contains("<td>&nbsp;No Differences Found&nbsp;</td>"));
+    assert!(result.contains("<th colspan=\"2\" class=\"diff_header\">a</th>"));
+}
+
+#[test]
+fn test_ndiff() {
+    let first_text = vec!["\tabc def\n"];
+    let second_text = vec!["\tabc  de\n"];
+    let result = difflib::ndiff(
+        &first_text,
+        &second_text,
+        None,
+        Some(Box::new(difflib::is_character_junk)),
+    ).join("");
+    assert_eq!(result, "- \tabc def\n? \t      -\n+ \tabc  de\n? \t    +\n");
+}
+
+#[test]
+fn test_junk_predicates() {
+    assert!(difflib::is_line_junk(&"\n"));
+    assert!(difflib::is_line_junk(&"  #   \n"));
+    assert!(!difflib::is_line_junk(&"hello\n"));
+    assert!(!difflib::is_line_junk(&"## \n"));
+    assert!(difflib::is_character_junk(&' '));
+    assert!(difflib::is_character_junk(&'\t'));
+    assert!(!difflib::is_character_junk(&'\n'));
+    assert!(!difflib::is_character_junk(&'x'));
+}
+
+#[test]
+fn test_quick_ratio() {
+    let mut matcher = SequenceMatcher::new("aabc", "abbd");
+    assert_eq!(matcher.quick_ratio(), 0.5);
+    assert_eq!(matcher.real_quick_ratio(), 1.0);
+    matcher.set_seqs("ab", "abcd");
+    assert!((matcher.quick_ratio() - 2.0 / 3.0).abs() < 1e-6);
+    assert!((matcher.real_quick_ratio() - 2.0 / 3.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_autojunk() {
+    let first_text = "xxabxxab";
+    let second_text = format!("xxx{}", "ab".repeat(100));
+    let mut matcher = SequenceMatcher::new(first_text, second_text.as_str());
+    let mut popular: Vec<u8> = matcher
+        .second_sequence_popular()
+        .iter()
+        .map(|&&c| c)
+        .collect();
+    popular.sort();
+    assert_eq!(popular, vec![b'a', b'b']);
+    assert_eq!(
+        matcher.get_matching_blocks(),
+        vec![
+            Match {
+                first_start: 0,
+                second_start: 0,
+                size: 2,
+            },
+            Match {
+                first_start: 4,
+                second_start: 2,
+                size: 1,
+            },
+            Match {
+                first_start: 8,
+                second_start: 203,
+                size: 0,
+            },
+        ]
+    );
+
+    let mut matcher = SequenceMatcher::with_autojunk(first_text, second_text.as_str(), false);
+    assert!(matcher.second_sequence_popular().is_empty());
+    assert_eq!(
+        matcher.get_matching_blocks(),
+        vec![
+            Match {
+                first_start: 0,
+                second_start: 1,
+                size: 4,
+            },
+            Match {
+                first_start: 6,
+                second_start: 5,
+                size: 2,
+            },
+            Match {
+                first_start: 8,
+                second_start: 203,
+                size: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_autojunk_keeps_unpopular_elements() {
+    // Only the popular elements are dropped from the index, the others
+    // can still be matched.
+    let second_text = format!("{}y", "x".repeat(199));
+    let mut matcher = SequenceMatcher::new("y", second_text.as_str());
+    assert_eq!(
+        matcher.get_matching_blocks(),
+        vec![
+            Match {
+                first_start: 0,
+                second_start: 199,
+                size: 1,
+            },
+            Match {
+                first_start: 1,
+                second_start: 200,
+                size: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_second_sequence_junk() {
+    let mut matcher = SequenceMatcher::new("a b", "a  c");
+    matcher.set_is_junk(Some(|c: &u8| *c == b' '));
+    assert_eq!(
+        matcher.second_sequence_junk().iter().collect::<Vec<_>>(),
+        vec![&&b' ']
+    );
+}
+
+#[test]
+fn test_tag_display() {
+    assert_eq!(Tag::Replace.to_string(), "replace");
+    assert_eq!(Tag::Delete.to_string(), "delete");
+    assert_eq!(Tag::Insert.to_string(), "insert");
+    assert_eq!(Tag::Equal.to_string(), "equal");
+}
+
+#[test]
+fn test_junk_closures() {
+    let junk_chars: HashSet<char> = [' ', '\t'].iter().cloned().collect();
+    let mut differ = Differ::new();
+    differ.char_junk = Some(Box::new(move |ch: &char| junk_chars.contains(ch)));
+    let result = differ.compare(&["\tabc def\n"], &["\tabc  de\n"]).join("");
+    assert_eq!(result, "- \tabc def\n? \t      -\n+ \tabc  de\n? \t    +\n");
+
+    let junk_byte = b'x';
+    let mut matcher = SequenceMatcher::new("axb", "axxb");
+    matcher.set_is_junk(Some(|c: &u8| *c == junk_byte));
+    assert_eq!(matcher.second_sequence_junk().len(), 1);
+}
+
+#[test]
+fn test_diff_iterators() {
+    let first_text = "one two three four".split(" ").collect::<Vec<&str>>();
+    let second_text = "zero one tree four".split(" ").collect::<Vec<&str>>();
+    let mut unified = difflib::unified_diff_iter(
+        &first_text,
+        &second_text,
+        "Original",
+        "Current",
+        "2005-01-26 23:30:50",
+        "2010-04-02 10:20:52",
+        3,
+    );
+    assert_eq!(
+        unified.next(),
+        Some("--- Original\t2005-01-26 23:30:50\n".to_string())
+    );
+    assert_eq!(
+        unified.collect::<Vec<String>>(),
+        &difflib::unified_diff(
+            &first_text,
+            &second_text,
+            "Original",
+            "Current",
+            "2005-01-26 23:30:50",
+            "2010-04-02 10:20:52",
+            3,
+        )[1..]
+    );
+    let context = difflib::context_diff_iter(&first_text, &second_text, "a", "b", "", "", 1);
+    assert_eq!(
+        context.collect::<Vec<String>>(),
+        difflib::context_diff(&first_text, &second_text, "a", "b", "", "", 1)
+    );
+    let first_text = vec!["one\n", "two\n", "three\n"];
+    let second_text = vec!["ore\n", "tree\n", "emu\n"];
+    let differ = Differ::new();
+    assert_eq!(
+        differ
+            .compare_iter(&first_text, &second_text)
+            .collect::<Vec<String>>(),
+        differ.compare(&first_text, &second_text)
+    );
+}
+
+#[test]
+fn test_write_diff() {
+    let first_text = vec!["one\n", "two\n", "three\n"];
+    let second_text = vec!["ore\n", "tree\n", "emu\n"];
+    let diff = difflib::unified_diff_iter(&first_text, &second_text, "a", "b", "", "", 0);
+    let mut bytes = Vec::new();
+    difflib::write_diff(&mut bytes, diff).unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        "--- a\t\n+++ b\t\n@@ -1,3 +1,3 @@\n-one\n-two\n-three\n+ore\n+tree\n+emu\n"
+    );
+    let differ = Differ::new();
+    let mut text = String::new();
+    difflib::write_diff_fmt(&mut text, differ.compare_iter(&first_text, &second_text)).unwrap();
+    assert_eq!(
+        text,
+        "- one\n?  ^\n+ ore\n?  ^\n- two\n- three\n?  -\n+ tree\n+ emu\n"
+    );
+}
+
+#[test]
+fn test_myers_matching_blocks() {
+    let mut matcher = SequenceMatcher::new("abcabba", "cbabac");
+    matcher.set_algorithm(Algorithm::Myers);
+    assert_eq!(
+        matcher.get_matching_blocks(),
+        vec![
+            Match {
+                first_start: 2,
+                second_start: 0,
+                size: 1,
+            },
+            Match {
+                first_start: 4,
+                second_start: 1,
+                size: 1,
+            },
+            Match {
+                first_start: 5,
+                second_start: 3,
+                size: 2,
+            },
+            Match {
+                first_start: 7,
+                second_start: 6,
+                size: 0,
+            },
+        ]
+    );
+    assert_eq!(matcher.ratio(), 8.0 / 13.0);
+}
+
+#[test]
+fn test_unified_diff_with_myers() {
+    let first_text = vec!["a\n", "b\n", "c\n", "a\n", "b\n", "b\n", "a\n"];
+    let second_text = vec!["c\n", "b\n", "a\n", "b\n", "a\n", "c\n"];
+    let options = difflib::DiffOptions {
+        n: 1,
+        algorithm: Algorithm::Myers,
+        ..difflib::DiffOptions::new()
+    };
+    let result = difflib::unified_diff_with_options(
+        &first_text,
+        &second_text,
+        "a",
+        "b",
+        "",
+        "",
+        &options,
+    ).collect::<String>();
+    assert_eq!(
+        result,
+        "--- a\t\n+++ b\t\n@@ -1,7 +1,6 @@\n-a\n-b\n c\n-a\n b\n+a\n b\n a\n+c\n"
+    );
+    let mut differ = Differ::new();
+    differ.algorithm = Algorithm::Myers;
+    assert_eq!(
+        differ.compare(&first_text, &second_text).join(""),
+        "- a\n- b\n  c\n- a\n  b\n+ a\n  b\n  a\n+ c\n"
+    );
+}
+
+#[test]
+fn test_patience_and_histogram_diff() {
+    let first_text = vec![
+        "int f() {\n",
+        "    return 1;\n",
+        "}\n",
+        "\n",
+        "int g() {\n",
+        "    return 2;\n",
+        "}\n",
+    ];
+    let second_text = vec![
+        "int f() {\n",
+        "    return 1;\n",
+        "}\n",
+        "\n",
+        "int h() {\n",
+        "    return 3;\n",
+        "}\n",
+        "\n",
+        "int g() {\n",
+        "    return 2;\n",
+        "}\n",
+    ];
+    for &algorithm in &[Algorithm::Patience, Algorithm::Histogram] {
+        let options = difflib::DiffOptions {
+            n: 0,
+            algorithm,
+            ..difflib::DiffOptions::new()
+        };
+        let result = difflib::unified_diff_with_options(
+            &first_text,
+            &second_text,
+            "a",
+            "b",
+            "",
+            "",
+            &options,
+        ).collect::<String>();
+        assert_eq!(
+            result,
+            "--- a\t\n+++ b\t\n@@ -4,0 +5,4 @@\n+int h() {\n+    return 3;\n+}\n+\n"
+        );
+    }
+}
+
+#[test]
+fn test_patience_opcodes() {
+    let first_text = vec!["a", "x", "b", "x", "c"];
+    let second_text = vec!["b", "x", "a", "x", "c"];
+    let mut matcher = SequenceMatcher::new(&first_text, &second_text);
+    matcher.set_algorithm(Algorithm::Patience);
+    let tags: Vec<Tag> = matcher.get_opcodes().iter().map(|o| o.tag).collect();
+    assert_eq!(
+        tags,
+        vec![Tag::Delete, Tag::Equal, Tag::Insert, Tag::Equal]
+    );
+}
+
+#[test]
+fn test_patch_parse() {
+    let first_text = "one\ntwo\nthree\nfour\n".split_inclusive('\n').collect::<Vec<_>>();
+    let second_text = "zero\none\nthree\nfour".split_inclusive('\n').collect::<Vec<_>>();
+    let mut diff = difflib::unified_diff(&first_text, &second_text, "a", "b", "", "", 3);
+    diff.insert(0, "diff -u a b\n".to_string());
+    let text = diff.join("");
+    let patch = Patch::parse(&text).unwrap();
+    assert_eq!(patch.files.len(), 1);
+    let file = &patch.files[0];
+    assert_eq!(file.header, vec!["diff -u a b\n"]);
+    assert_eq!(file.from_file, "a");
+    assert_eq!(file.to_file_date, Some(String::new()));
+    let hunk = &file.hunks[0];
+    assert_eq!(
+        (hunk.from_start, hunk.from_count, hunk.to_start, hunk.to_count),
+        (1, 4, 1, 4)
+    );
+    let kinds: Vec<LineKind> = hunk.lines.iter().map(|l| l.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            LineKind::Added,
+            LineKind::Context,
+            LineKind::Removed,
+            LineKind::Context,
+            LineKind::Removed,
+            LineKind::Added,
+        ]
+    );
+    assert_eq!(hunk.lines[5].text, "four");
+
+    let text = "--- a\n+++ b\n@@ -1,2 +1 @@\n-one\n-two\n\\ No newline at end of file\n+one\n";
+    let patch: Patch = text.parse().unwrap();
+    assert_eq!(patch.files[0].hunks[0].lines[1].text, "two");
+    assert_eq!(patch.to_string(), text);
+}
+
+#[test]
+fn test_patch_parse_errors() {
+    let error = Patch::parse("--- a\n+++ b\n@@ -1,x +1 @@\n").unwrap_err();
+    assert_eq!((error.line, error.column), (3, 7));
+    assert_eq!(error.to_string(), "3:7: invalid range");
+    let error = Patch::parse("--- a\n+++ b\n@@ -1,2 +1,2 @@\n one\n").unwrap_err();
+    assert_eq!(error.line, 5);
+    assert_eq!(error.message, "unexpected end of hunk");
+    let error = Patch::parse("--- a\n+++ b\n").unwrap_err();
+    assert_eq!(error.message, "expected hunk header");
+    assert!(Patch::parse("no patch here\n").unwrap().files.is_empty());
+}
+
+#[test]
+fn test_patch_apply() {
+    let first_text = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n"
+        .split_inclusive('\n')
+        .collect::<Vec<_>>();
+    let second_text = "a\nB\nc\nd\ne\nf\ng\nh\ni\nJ\nk\n"
+        .split_inclusive('\n')
+        .collect::<Vec<_>>();
+    let unified = difflib::unified_diff(&first_text, &second_text, "a", "b", "", "", 1).join("");
+    let context = difflib::context_diff(&first_text, &second_text, "a", "b", "", "", 1).join("");
+    for text in &[unified, context] {
+        let patch = Patch::parse(text).unwrap();
+        assert_eq!(&patch.to_string(), text);
+        let result = patch.files[0].apply(&first_text);
+        assert!(result.is_clean());
+        assert_eq!(result.lines, second_text);
+    }
+    let patch = Patch::parse(&difflib::context_diff(
+        &first_text,
+        &second_text,
+        "a",
+        "b",
+        "",
+        "",
+        1,
+    ).join("")).unwrap();
+    assert_eq!(patch.files[0].format, Format::Context);
+
+    let mut shifted = vec!["new\n", "new\n"];
+    shifted.extend(first_text.iter().cloned());
+    let file = &patch.files[0];
+    let result = file.apply(&shifted);
+    assert_eq!(result.hunks, vec![HunkResult::Rejected, HunkResult::Rejected]);
+    assert_eq!(result.lines, shifted);
+    let result = file.apply_with_options(&shifted, &ApplyOptions::new());
+    assert_eq!(
+        result.hunks,
+        vec![
+            HunkResult::AppliedWithOffset { offset: 2 },
+            HunkResult::AppliedWithOffset { offset: 2 },
+        ]
+    );
+    assert_eq!(&result.lines[2..], &second_text[..]);
+
+    let mut edited = first_text.clone();
+    edited[8] = "changed\n";
+    let result = file.apply_with_options(&edited, &ApplyOptions::new());
+    assert_eq!(
+        result.hunks,
+        vec![
+            HunkResult::Applied,
+            HunkResult::AppliedWithFuzz { offset: 0, fuzz: 1 },
+        ]
+    );
+    assert_eq!(result.lines[8], "changed\n");
+    assert_eq!(result.lines[9], "J\n");
+    let options = ApplyOptions {
+        fuzz: 0,
+        ..ApplyOptions::new()
+    };
+    let result = file.apply_with_options(&edited, &options);
+    assert_eq!(result.hunks[1], HunkResult::Rejected);
+    assert_eq!(result.lines[9], "j\n");
+}
+
+#[test]
+fn test_patch_invert() {
+    let first_text = "one\ntwo\nthree\n".split_inclusive('\n').collect::<Vec<_>>();
+    let second_text = "one\n2\nthree\nfour\n".split_inclusive('\n').collect::<Vec<_>>();
+    let diff = difflib::unified_diff(&first_text, &second_text, "a", "b", "1", "2", 3).join("");
+    let inverted = Patch::parse(&diff).unwrap().invert();
+    assert_eq!(
+        inverted.to_string(),
+        "--- b\t2\n+++ a\t1\n@@ -1,4 +1,3 @@\n one\n-2\n+two\n three\n-four\n"
+    );
+    let result = inverted.files[0].apply(&second_text);
+    assert_eq!(result.lines, first_text);
+
+    let diff = difflib::context_diff(&first_text, &second_text, "a", "b", "1", "2", 3).join("");
+    let inverted = Patch::parse(&diff).unwrap().invert();
+    assert_eq!(
+        inverted.to_string(),
+        "*** b\t2\n--- a\t1\n***************\n*** 1,4 ****\n  one\n! 2\n  three\n- four\n\
+         --- 1,3 ----\n  one\n! two\n  three\n"
+    );
+}
+
+#[test]
+fn test_intraline_diffs() {
+    let first_text = vec!["one\n", "h\u{e9}llo w\u{f6}rld\n", "three\n"];
+    let second_text = vec!["one\n", "h\u{e9}llo wurld!\n", "four\n"];
+    let differ = Differ::new();
+    let diffs = differ.intraline_diffs(&first_text, &second_text);
+    assert_eq!(
+        diffs,
+        vec![IntralineDiff {
+            first_line: 1,
+            second_line: 1,
+            changes: vec![
+                Opcode {
+                    tag: Tag::Replace,
+                    first_start: 7,
+                    first_end: 8,
+                    second_start: 7,
+                    second_end: 8,
+                },
+                Opcode {
+                    tag: Tag::Insert,
+                    first_start: 11,
+                    first_end: 11,
+                    second_start: 11,
+                    second_end: 12,
+                },
+            ],
+        }]
+    );
+    let byte_changes = diffs[0].byte_changes(first_text[1], second_text[1]);
+    assert_eq!(
+        (byte_changes[0].first_start, byte_changes[0].first_end),
+        (8, 10)
+    );
+    assert_eq!(&second_text[1][byte_changes[0].second_start..byte_changes[0].second_end], "u");
+    assert_eq!(&second_text[1][byte_changes[1].second_start..byte_changes[1].second_end], "!");
+    assert_eq!(differ.intraline_diffs(&["same\n"], &["same\n"]), vec![]);
+}
+
+#[test]
+fn test_differ_invert() {
+    let first_text = vec!["one\n", "two\n", "three\n"];
+    let second_text = vec!["ore\n", "tree\n", "emu\n"];
+    let differ = Differ::new();
+    let delta = differ.compare(&first_text, &second_text);
+    let inverted = Differ::invert(&delta);
+    assert_eq!(
+        inverted,
+        vec![
+            "- ore\n", "?  ^\n", "+ one\n", "?  ^\n", "+ two\n", "- tree\n", "+ three\n", "?  +\n",
+            "- emu\n",
+        ]
+    );
+    assert_eq!(inverted, differ.compare(&second_text, &first_text));
+    assert_eq!(Differ::restore(&inverted, 1), Differ::restore(&delta, 2));
+    assert_eq!(Differ::restore(&inverted, 2), first_text);
+}
+
+#[test]
+fn test_merge() {
+    let base = vec!["a\n", "b\n", "c\n", "d\n", "e\n"];
+    let ours = vec!["a\n", "B\n", "c\n", "d\n", "e\n"];
+    let theirs = vec!["a\n", "b\n", "c\n", "d\n", "E\n"];
+    let result = difflib::merge::merge(&base, &ours, &theirs);
+    assert!(result.is_clean());
+    assert_eq!(result.lines, vec!["a\n", "B\n", "c\n", "d\n", "E\n"]);
+
+    let theirs = vec!["a\n", "x\n", "B\n", "y\n", "c\n", "d\n", "e\n"];
+    let result = difflib::merge::merge(&base, &ours, &theirs);
+    assert_eq!(
+        result.lines.concat(),
+        "a\n<<<<<<< ours\nB\n=======\nx\nB\ny\n>>>>>>> theirs\nc\nd\ne\n"
+    );
+    let conflict = &result.conflicts[0];
+    assert_eq!(conflict.line, 1);
+    assert_eq!(
+        (conflict.base_start, conflict.ours_start, conflict.theirs_start),
+        (1, 1, 1)
+    );
+    assert_eq!(conflict.base, vec!["b\n"]);
+    assert_eq!(conflict.ours, vec!["B\n"]);
+    assert_eq!(conflict.theirs, vec!["x\n", "B\n", "y\n"]);
+}
+
+#[test]
+fn test_merge_conflict_styles() {
+    let base = vec!["a\n", "b\n", "c\n"];
+    let ours = vec!["a\n", "x\n", "y\n", "c\n"];
+    let theirs = vec!["a\n", "x\n", "z\n", "c\n"];
+    let mut options = MergeOptions::new();
+    let result = difflib::merge::merge_with_options(&base, &ours, &theirs, &options);
+    assert_eq!(
+        result.lines.concat(),
+        "a\nx\n<<<<<<< ours\ny\n=======\nz\n>>>>>>> theirs\nc\n"
+    );
+    assert_eq!(result.conflicts[0].ours_start, 2);
+
+    options.style = ConflictStyle::Diff3;
+    options.ours_label = String::new();
+    let result = difflib::merge::merge_with_options(&base, &ours, &theirs, &options);
+    assert_eq!(
+        result.lines.concat(),
+        "a\n<<<<<<<\nx\ny\n||||||| base\nb\n=======\nx\nz\n>>>>>>> theirs\nc\n"
+    );
+
+    options.style = ConflictStyle::ZealousDiff3;
+    let result = difflib::merge::merge_with_options(&base, &ours, &theirs, &options);
+    assert_eq!(
+        result.lines.concat(),
+        "a\nx\n<<<<<<<\ny\n||||||| base\nb\n=======\nz\n>>>>>>> theirs\nc\n"
+    );
+}
+
+#[test]
+fn test_diff_no_newline_at_end_of_file() {
+    let first_text = vec!["one\n", "two\n", "three"];
+    let second_text = vec!["one\n", "two\n", "three\n"];
+    let result = difflib::unified_diff(&first_text, &second_text, "a", "b", "", "", 1).join("");
+    assert_eq!(
+        result,
+        "--- a\t\n+++ b\t\n@@ -2,2 +2,2 @@\n two\n-three\n\\ No newline at end of file\n+three\n"
+    );
+    let patch = Patch::parse(&result).unwrap();
+    assert_eq!(patch.files[0].apply(&first_text).lines, second_text);
+
+    let result = difflib::context_diff(&second_text, &first_text, "a", "b", "", "", 0).join("");
+    assert_eq!(
+        result,
+        "*** a\t\n--- b\t\n***************\n*** 3 ****\n! three\n--- 3 ----\n! three\n\
+         \\ No newline at end of file\n"
+    );
+
+    let options = difflib::DiffOptions {
+        n: 0,
+        lineterm: String::new(),
+        ..difflib::DiffOptions::new()
+    };
+    let first_text = vec!["one", "two"];
+    let second_text = vec!["one", "three"];
+    let result: Vec<String> = difflib::unified_diff_with_options(
+        &first_text,
+        &second_text,
+        "a",
+        "b",
+        "",
+        "",
+        &options,
+    ).collect();
+    assert_eq!(
+        result,
+        vec!["--- a\t", "+++ b\t", "@@ -2 +2 @@", "-two", "+three"]
+    );
+}
+
+#[test]
+fn test_git_diff() {
+    let first_text = vec!["one\n", "two\n", "three\n"];
+    let second_text = vec!["one\n", "2\n", "three\n"];
+    let old = GitFile::new("file.txt", &first_text);
+    let new = GitFile::new("file.txt", &second_text);
+    let options = GitOptions::new();
+    assert_eq!(
+        difflib::git::diff(Some(&old), Some(&new), &options).concat(),
+        "diff --git a/file.txt b/file.txt\nindex 4cb29ea..f04eb26 100644\n--- a/file.txt\n\
+         +++ b/file.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"
+    );
+    assert!(difflib::git::diff(Some(&old), Some(&old), &options).is_empty());
+
+    assert_eq!(
+        difflib::git::diff(None, Some(&old), &options).concat(),
+        "diff --git a/file.txt b/file.txt\nnew file mode 100644\nindex 0000000..4cb29ea\n\
+         --- /dev/null\n+++ b/file.txt\n@@ -0,0 +1,3 @@\n+one\n+two\n+three\n"
+    );
+    let empty = GitFile::new("empty", &[]);
+    assert_eq!(
+        difflib::git::diff(Some(&empty), None, &options).concat(),
+        "diff --git a/empty b/empty\ndeleted file mode 100644\nindex e69de29..0000000\n"
+    );
+
+    let renamed = GitFile {
+        path: "renamed.txt",
+        mode: EXECUTABLE_FILE_MODE,
+        ..new
+    };
+    assert_eq!(
+        difflib::git::diff(Some(&old), Some(&renamed), &options)[..5].concat(),
+        "diff --git a/file.txt b/renamed.txt\nold mode 100644\nnew mode 100755\n\
+         similarity index 66%\nrename from file.txt\n"
+    );
+
+    // Below the rename threshold the old file is deleted and a new one added.
+    let other_text = vec!["four\n", "five\n"];
+    let unrelated = GitFile::new("other.txt", &other_text);
+    assert_eq!(
+        difflib::git::diff(Some(&old), Some(&unrelated), &options).concat(),
+        "diff --git a/file.txt b/file.txt\ndeleted file mode 100644\nindex 4cb29ea..0000000\n\
+         --- a/file.txt\n+++ /dev/null\n@@ -1,3 +0,0 @@\n-one\n-two\n-three\n\
+         diff --git a/other.txt b/other.txt\nnew file mode 100644\nindex 0000000..da7532d\n\
+         --- /dev/null\n+++ b/other.txt\n@@ -0,0 +1,2 @@\n+four\n+five\n"
+    );
+    let mut strict = GitOptions::new();
+    strict.rename_threshold = 70;
+    let diff = difflib::git::diff(Some(&old), Some(&renamed), &strict);
+    assert_eq!(diff[0], "diff --git a/file.txt b/file.txt\n");
+    assert_eq!(diff[1], "deleted file mode 100644\n");
+    assert!(diff.contains(&"new file mode 100755\n".to_string()));
+}
+
+#[test]
+fn test_normal_diff() {
+    let first_text = vec!["a\n", "b\n", "c\n", "d\n", "e"];
+    let second_text = vec!["a\n", "B\n", "X\n", "c\n", "e\n", "f\n"];
+    assert_eq!(
+        difflib::normal_diff(&first_text, &second_text).concat(),
+        "2c2,3\n< b\n---\n> B\n> X\n4,5c5,6\n< d\n< e\n\\ No newline at end of file\n---\n> e\n> f\n"
+    );
+    let second_text = vec!["x\n", "a\n", "d\n", "e"];
+    assert_eq!(
+        difflib::normal_diff(&first_text, &second_text).concat(),
+        "0a1\n> x\n2,3d2\n< b\n< c\n"
+    );
+}
+
+#[test]
+fn test_ed_diff() {
+    let first_text = vec!["a\n", "b\n", "c\n", "d\n"];
+    let second_text = vec!["a\n", ".\n", "x\n", "c\n"];
+    assert_eq!(
+        difflib::ed_diff(&first_text, &second_text).concat(),
+        "4d\n2c\n..\n.\ns/.//\na\nx\n.\n"
+    );
+    let second_text = vec!["z\n", "a\n", "b\n", "c\n", "d\n"];
+    assert_eq!(
+        difflib::ed_diff(&first_text, &second_text).concat(),
+        "0a\nz\n.\n"
+    );
+}
+
+#[test]
+fn test_side_by_side() {
+    let first_text = vec!["same\n", "apple pie\n", "banana\n", "a very long line of text\n"];
+    let second_text = vec!["same\n", "cherry\n", "apple pies\n", "a very long line of text"];
+    let mut side_by_side = SideBySideDiff::new();
+    side_by_side.width = 40;
+    side_by_side.expand_tabs = true;
+    assert_eq!(
+        side_by_side.make_lines(&first_text, &second_text),
+        vec![
+            "same                  same\n",
+            "                   >  cherry\n",
+            "apple pie          |  apple pies\n",
+            "banana             <\n",
+            "a very long line o /  a very long line o\n",
+        ]
+    );
+    side_by_side.suppress_common_lines = true;
+    assert_eq!(side_by_side.make_lines(&first_text, &second_text).len(), 4);
+    side_by_side.suppress_common_lines = false;
+    side_by_side.left_column = true;
+    side_by_side.expand_tabs = false;
+    assert_eq!(
+        side_by_side.make_lines(&first_text, &second_text)[0],
+        "same\t\t   (\n"
+    );
+}
+
+#[test]
+fn test_color_diff() {
+    let first_text = vec!["keep\n", "hello world\n", "gone\n"];
+    let second_text = vec!["keep\n", "hello wurld\n"];
+    let diff = difflib::unified_diff(&first_text, &second_text, "a", "b", "", "", 3);
+    let mut color = ColorDiff::new();
+    assert_eq!(
+        color.render(&diff),
+        vec![
+            "\x1b[1m--- a\t\x1b[0m\n",
+            "\x1b[1m+++ b\t\x1b[0m\n",
+            "\x1b[36m@@ -1,3 +1,2 @@\x1b[0m\n",
+            " keep\n",
+            "\x1b[31m-hello w\x1b[7;31mo\x1b[0m\x1b[31mrld\x1b[0m\n",
+            "\x1b[31m-gone\x1b[0m\n",
+            "\x1b[32m+hello w\x1b[7;32mu\x1b[0m\x1b[32mrld\x1b[0m\n",
+        ]
+    );
+    color.theme = Theme {
+        removed_highlight: String::new(),
+        added_highlight: String::new(),
+        ..Theme::new()
+    };
+    assert_eq!(color.render(&diff)[4], "\x1b[31m-hello world\x1b[0m\n");
+    color.enabled = false;
+    assert_eq!(color.render(&diff), diff);
+
+    // Lines past the counts of a malformed hunk are left as header lines.
+    color.enabled = true;
+    assert_eq!(
+        color.render(&["@@ -0,0 +1,2 @@\n", "+a\n", "-b\n", "+c\n"]),
+        vec![
+            "\x1b[36m@@ -0,0 +1,2 @@\x1b[0m\n",
+            "\x1b[32m+a\x1b[0m\n",
+            "\x1b[1m-b\x1b[0m\n",
+            "\x1b[1m+c\x1b[0m\n",
+        ]
+    );
+}
+
+#[test]
+fn test_word_diff_tokenizers() {
+    let text = "fn main() {  x_1+=2;\n}";
+    assert_eq!(
+        Tokenizer::Whitespace.tokenize(text),
+        vec!["fn", " ", "main()", " ", "{", "  ", "x_1+=2;", "\n", "}"]
+    );
+    assert_eq!(
+        Tokenizer::UnicodeWords.tokenize("gr\u{fc}\u{df}e, x_1+=2\n"),
+        vec!["gr\u{fc}\u{df}e", ",", " ", "x_1", "+", "=", "2", "\n"]
+    );
+    // Word boundaries follow UAX #29 rather than character classes.
+    assert_eq!(
+        Tokenizer::UnicodeWords.tokenize("can't 3.14  \u{65e5}\u{672c}"),
+        vec!["can't", " ", "3.14", "  ", "\u{65e5}", "\u{672c}"]
+    );
+    let custom = Tokenizer::Custom(Box::new(|line: &str| line.split_inclusive(',').collect()));
+    assert_eq!(custom.tokenize("a,b,c"), vec!["a,", "b,", "c"]);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_word_diff_regex_tokenizer() {
+    let regex = Tokenizer::Regex(Regex::new("[a-z]+").unwrap());
+    assert_eq!(
+        regex.tokenize("ab12cd\n\nef"),
+        vec!["ab", "12", "cd", "\n", "\n", "ef"]
+    );
+}
+
+#[test]
+fn test_word_diff() {
+    let first_text = vec!["The quick brown fox\n", "jumps over\n", "the lazy dog.\n"];
+    let second_text = vec!["The quick red fox\n", "jumps over\n", "the lazy cat.\n", "new\n"];
+    let mut word_diff = WordDiff::new();
+    assert_eq!(
+        word_diff.make_lines(&first_text, &second_text),
+        vec![
+            "@@ -1,3 +1,4 @@\n",
+            "The quick [-brown-]{+red+} fox\n",
+            "jumps over\n",
+            "the lazy [-dog.-]{+cat.+}\n",
+            "{+new+}\n",
+        ]
+    );
+    assert_eq!(word_diff.diff_text("a b", "a c"), "a [-b-]{+c+}");
+    word_diff.mode = WordDiffMode::Porcelain;
+    assert_eq!(
+        word_diff.diff_text("one two\n", "one three\n"),
+        " one \n-two\n+three\n~\n"
+    );
+    word_diff.mode = WordDiffMode::Color;
+    assert_eq!(
+        word_diff.make_lines(&["a b\n"], &["a c\n"]),
+        vec!["\x1b[36m@@ -1 +1 @@\x1b[0m\n", "a \x1b[31mb\x1b[0m\x1b[32mc\x1b[0m\n"]
+    );
+    assert!(word_diff.make_lines(&first_text, &first_text).is_empty());
+}
+
+#[test]
+fn test_compare_options() {
+    let mut compare = CompareOptions::new();
+    assert!(!compare.normalizes());
+    assert_eq!(compare.normalize(" a  B \r\n"), " a  B \r\n");
+    compare.ignore_space_change = true;
+    assert_eq!(compare.normalize("  a \t B \n"), " a B\n");
+    compare.ignore_space_change = false;
+    compare.ignore_all_space = true;
+    compare.ignore_case = true;
+    assert_eq!(compare.normalize("  a \t B \n"), "ab\n");
+    compare = CompareOptions {
+        strip_trailing_cr: true,
+        ..CompareOptions::new()
+    };
+    assert_eq!(compare.normalize("a\r\n"), "a\n");
+    assert_eq!(compare.normalize("a\r"), "a");
+
+    let first_text = vec!["if x:\n", "    Return  1\n", "end\n"];
+    let second_text = vec!["if x:\n", "  return 1\n", "\n", "end\n"];
+    let mut options = difflib::DiffOptions::new();
+    options.compare.ignore_all_space = true;
+    let diff =
+        difflib::unified_diff_with_options(&first_text, &second_text, "", "", "", "", &options);
+    assert_eq!(
+        diff.skip(2).collect::<Vec<String>>(),
+        vec![
+            "@@ -1,3 +1,4 @@\n",
+            " if x:\n",
+            "-    Return  1\n",
+            "+  return 1\n",
+            "+\n",
+            " end\n",
+        ]
+    );
+    options.compare.ignore_case = true;
+    let diff =
+        difflib::unified_diff_with_options(&first_text, &second_text, "", "", "", "", &options);
+    assert_eq!(
+        diff.skip(2).collect::<Vec<String>>(),
+        vec!["@@ -1,3 +1,4 @@\n", " if x:\n", "     Return  1\n", "+\n", " end\n"]
+    );
+    options.compare.ignore_blank_lines = true;
+    assert_eq!(
+        difflib::unified_diff_with_options(&first_text, &second_text, "", "", "", "", &options)
+            .count(),
+        0
+    );
+    assert!(difflib::normal_diff_with_options(&first_text, &second_text, &options)
+        .next()
+        .is_none());
+
+    let mut differ = Differ::new();
+    differ.compare.ignore_space_change = true;
+    differ.compare.ignore_case = true;
+    assert_eq!(
+        differ.compare(&first_text, &second_text[..2]),
+        vec!["  if x:\n", "      Return  1\n", "- end\n"]
+    );
+    // Changes to ignored lines alone are left out, others are written.
+    differ.compare.ignore_blank_lines = true;
+    assert_eq!(
+        differ.compare(&["a\n", "b\n", "c\n"], &["a\n", "\n", "b\n", "d\n", "\n"]),
+        vec!["  a\n", "  b\n", "- c\n", "+ d\n", "+ \n"]
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_ignore_matching_lines() {
+    let first_text = vec![
+        "// Generated at 10:00\n",
+        "a\n",
+        "b\n",
+        "c\n",
+        "d\n",
+        "e\n",
+        "f\n",
+        "g\n",
+        "h\n",
+        "build 17\n",
+    ];
+    let mut second_text = first_text.clone();
+    second_text[0] = "// Generated at 11:30\n";
+    second_text[9] = "build 18\n";
+    let mut options = difflib::DiffOptions::new();
+    options.compare.ignore_matching_lines =
+        vec![Regex::new("^// Generated").unwrap()];
+    let diff: Vec<String> =
+        difflib::unified_diff_with_options(&first_text, &second_text, "", "", "", "", &options)
+            .skip(2)
+            .collect();
+    assert_eq!(
+        diff,
+        vec!["@@ -7,4 +7,4 @@\n", " f\n", " g\n", " h\n", "-build 17\n", "+build 18\n"]
+    );
+    options
+        .compare
+        .ignore_matching_lines
+        .push(Regex::new("^build [0-9]+$").unwrap());
+    assert_eq!(
+        difflib::unified_diff_with_options(&first_text, &second_text, "", "", "", "", &options)
+            .count(),
+        0
+    );
+    assert!(options.compare.is_ignored_line("build 3\n"));
+    assert!(!options.compare.is_ignored_line("\n"));
+    options.compare.ignore_blank_lines = true;
+    assert!(options.compare.is_ignored_line("\n"));
+    let mut compare = options.compare.clone();
+    assert_eq!(compare, options.compare);
+    compare.ignore_matching_lines[1] = Regex::new("^build").unwrap();
+    assert_ne!(compare, options.compare);
+
+    // A hunk with a change that isn't ignored is written in full.
+    second_text[1] = "A\n";
+    let diff: Vec<String> =
+        difflib::unified_diff_with_options(&first_text, &second_text, "", "", "", "", &options)
+            .skip(2)
+            .collect();
+    assert_eq!(
+        diff,
+        vec![
+            "@@ -1,5 +1,5 @@\n",
+            "-// Generated at 10:00\n",
+            "-a\n",
+            "+// Generated at 11:30\n",
+            "+A\n",
+            " b\n",
+            " c\n",
+            " d\n",
+        ]
+    );
+
+    let mut differ = Differ::new();
+    differ.compare = options.compare.clone();
+    let delta = differ.compare(&first_text[..3], &["// Generated at 11:30\n", "a\n", "B\n"]);
+    assert_eq!(delta, vec!["  a\n", "- b\n", "+ B\n"]);
+}
+
+#[test]
+fn test_keyed_sequence_matcher() {
+    struct Record {
+        time: u32,
+        message: &'static str,
+    }
+    let first = vec![
+        Record { time: 1, message: "start" },
+        Record { time: 2, message: "load" },
+        Record { time: 3, message: "stop" },
+    ];
+    let second = vec![
+        Record { time: 7, message: "start" },
+        Record { time: 8, message: "save" },
+        Record { time: 9, message: "stop" },
+    ];
+    let mut matcher = KeyedSequenceMatcher::new(&first, &second, |record| record.message);
+    assert_eq!(matcher.first_keys(), &["start", "load", "stop"][..]);
+    let opcodes = matcher.get_opcodes();
+    assert_eq!(
+        opcodes
+            .iter()
+            .map(|opcode| (opcode.tag, opcode.first_start, opcode.second_start))
+            .collect::<Vec<_>>(),
+        vec![(Tag::Equal, 0, 0), (Tag::Replace, 1, 1), (Tag::Equal, 2, 2)]
+    );
+    assert_eq!(matcher.first_sequence()[opcodes[1].first_start].time, 2);
+    assert_eq!(matcher.second_sequence()[opcodes[1].second_start].message, "save");
+    assert_eq!(matcher.get_grouped_opcodes(0).len(), 1);
+    assert!((matcher.ratio() - 2.0 / 3.0).abs() < 1e-6);
+    assert!((matcher.quick_ratio() - 2.0 / 3.0).abs() < 1e-6);
+    assert_eq!(matcher.real_quick_ratio(), 1.0);
+
+    matcher.set_algorithm(Algorithm::Myers);
+    assert_eq!(matcher.get_opcodes(), opcodes);
+    matcher.set_is_junk(Some(|message: &&str| *message == "start"));
+    assert_eq!(
+        matcher.get_matching_blocks(),
+        vec![
+            Match {
+                first_start: 0,
+                second_start: 0,
+                size: 1
+            },
+            Match {
+                first_start: 2,
+                second_start: 2,
+                size: 1
+            },
+            Match {
+                first_start: 3,
+                second_start: 3,
+                size: 0
+            },
+        ]
+    );
+
+    let lowered = |line: &&str| line.to_lowercase();
+    let mut matcher = KeyedSequenceMatcher::new(&["A", "b"][..], &["a", "B", "c"][..], lowered);
+    assert_eq!(
+        matcher.get_opcodes(),
+        vec![
+            Opcode {
+                tag: Tag::Equal,
+                first_start: 0,
+                first_end: 2,
+                second_start: 0,
+                second_end: 2
+            },
+            Opcode {
+                tag: Tag::Insert,
+                first_start: 2,
+                first_end: 2,
+                second_start: 2,
+                second_end: 3
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_owned_sequence_matcher() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    let first: Arc<[String]> = vec!["a".to_string(), "b".to_string(), "c".to_string()].into();
+    let second = vec!["a".to_string(), "x".to_string(), "c".to_string()];
+    let mut matcher = OwnedSequenceMatcher::new(first.clone(), second.into());
+    assert_send_sync(&matcher);
+    let expected = SequenceMatcher::new(&["a", "b", "c"], &["a", "x", "c"]).get_opcodes();
+    let handle = thread::spawn(move || {
+        let opcodes = matcher.get_opcodes();
+        (matcher, opcodes)
+    });
+    let (mut matcher, opcodes) = handle.join().unwrap();
+    assert_eq!(opcodes, expected);
+    assert_eq!(matcher.get_grouped_opcodes(3).len(), 1);
+    assert!(Arc::ptr_eq(matcher.first_sequence(), &first));
+    assert!((matcher.ratio() - 2.0 / 3.0).abs() < 1e-6);
+    assert!((matcher.quick_ratio() - 2.0 / 3.0).abs() < 1e-6);
+    assert_eq!(matcher.real_quick_ratio(), 1.0);
+    assert_eq!(matcher.matcher().find_longest_match(0, 3, 0, 3).size, 1);
+
+    let clone = matcher.clone();
+    matcher.set_is_junk(Some(|item: &String| item == "a"));
+    matcher.set_second_seq(vec!["a".to_string(), "c".to_string()]);
+    assert_eq!(matcher.get_matching_blocks().len(), 3);
+    assert!(matcher.matcher().second_sequence_junk().contains(&"a".to_string()));
+    assert_eq!(clone.second_sequence().len(), 3);
+    assert_eq!(clone.matcher().get_matching_blocks().len(), 3);
+}
+
+#[test]
+fn test_sequence_matcher_index() {
+    let document: Vec<String> = (0..300).map(|i| format!("line {}\n", i % 150)).collect();
+    let index = Index::new(&document);
+    assert_eq!(index.sequence().len(), 300);
+    assert!(index.popular().is_empty());
+    let candidates: Vec<Vec<String>> = (0..4)
+        .map(|k| document.iter().skip(k * 10).cloned().collect())
+        .collect();
+    let shared: Vec<Vec<Opcode>> = thread::scope(|scope| {
+        let handles: Vec<_> = candidates
+            .iter()
+            .map(|candidate| {
+                let index = &index;
+                scope.spawn(move || SequenceMatcher::with_index(candidate, index).get_opcodes())
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    for (candidate, opcodes) in candidates.iter().zip(shared) {
+        assert_eq!(
+            opcodes,
+            SequenceMatcher::new(candidate, &document).get_opcodes()
+        );
+    }
+
+    let text = "private Thread currentThread;";
+    let index = Index::with_junk(text, Some(|c: &u8| *c == b' '), true);
+    assert_eq!(index.junk().len(), 1);
+    assert!(index.junk().contains(&b' '));
+    let mut matcher = SequenceMatcher::with_index("private volatile Thread currentThread;", &index);
+    assert_eq!(matcher.second_sequence_junk(), index.junk());
+    let mut expected = SequenceMatcher::new("private volatile Thread currentThread;", text);
+    expected.set_is_junk(Some(|c: &u8| *c == b' '));
+    assert_eq!(matcher.get_matching_blocks(), expected.get_matching_blocks());
+    assert!((matcher.ratio() - expected.ratio()).abs() < 1e-6);
+}
+
+#[test]
+fn test_side_by_side_degenerate_settings() {
+    let first_text = vec!["same\n", "a\tb\n", "gone\n"];
+    let second_text = vec!["same\n", "a\tc\n"];
+    let mut side_by_side = SideBySideDiff::new();
+    side_by_side.width = 0;
+    assert_eq!(
+        side_by_side.make_lines(&first_text, &second_text),
+        vec!["\n", "|\n", "<\n"]
+    );
+    side_by_side.width = 20;
+    side_by_side.tab_size = 1;
+    let expected = side_by_side.make_lines(&first_text, &second_text);
+    assert_eq!(expected[2], "gone\t\t\t\t\t<\n");
+    side_by_side.tab_size = 0;
+    assert_eq!(side_by_side.make_lines(&first_text, &second_text), expected);
+}
+
+#[test]
+fn test_junk_predicates_keep_types_send_sync() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    let mut differ = Differ::new();
+    differ.line_junk = Some(Box::new(|line: &&str| line.trim().is_empty()));
+    differ.char_junk = Some(Box::new(|c: &char| *c == ' '));
+    assert_send_sync(&differ);
+    assert_send_sync(&HtmlDiff::new());
+    assert_send_sync(&SideBySideDiff::new());
+    assert_send_sync(&ColorDiff::new());
+    assert_send_sync(&difflib::DiffOptions::new());
+    let mut matcher = SequenceMatcher::new("abc", "abd");
+    matcher.set_is_junk(Some(|c: &u8| *c == b'c'));
+    assert_send_sync(&matcher);
+    let handle = thread::spawn(move || differ.compare(&["a\n"], &["b\n"]));
+    assert_eq!(handle.join().unwrap(), vec!["- a\n", "+ b\n"]);
+}
+
+// Anchor prefixes count up over every table made in the process, the
+// expected outputs below come from Python's difflib.HtmlDiff, where the
+// table was the first one made.
+fn with_first_prefix(html: String) -> String {
+    let start = html.find("id=\"difflib_chg_to").unwrap() + "id=\"difflib_chg_to".len();
+    let n: String = html[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    html.replace(&format!("from{}_", n), "from0_")
+        .replace(&format!("to{}_", n), "to0_")
+}
+
+#[test]
+fn test_html_diff_matches_python_context() {
+    let first_text = vec![
+        "one\n", "two\n", "three\n", "four\n", "five\n", "six\n", "seven\n", "eight\n", "nine\n",
+        "ten\n",
+    ];
+    let mut second_text = first_text.clone();
+    second_text[1] = "TWO\n";
+    second_text.push("eleven\n");
+    let result = HtmlDiff::new().make_table(&first_text, &second_text, "before", "after", true, 1);
+    assert_eq!(
+        with_first_prefix(result),
+        r##"
+    <table class="diff" id="difflib_chg_to0__top"
+           cellspacing="0" cellpadding="0" rules="groups" >
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <thead><tr><th class="diff_next"><br /></th><th colspan="2" class="diff_header">before</th><th class="diff_next"><br /></th><th colspan="2" class="diff_header">after</th></tr></thead>
+        <tbody>
+            <tr><td class="diff_next" id="difflib_chg_to0__0"><a href="#difflib_chg_to0__0">f</a></td><td class="diff_header" id="from0_1">1</td><td nowrap="nowrap">one</td><td class="diff_next"><a href="#difflib_chg_to0__0">f</a></td><td class="diff_header" id="to0_1">1</td><td nowrap="nowrap">one</td></tr>
+            <tr><td class="diff_next"><a href="#difflib_chg_to0__1">n</a></td><td class="diff_header" id="from0_2">2</td><td nowrap="nowrap"><span class="diff_sub">two</span></td><td class="diff_next"><a href="#difflib_chg_to0__1">n</a></td><td class="diff_header" id="to0_2">2</td><td nowrap="nowrap"><span class="diff_add">TWO</span></td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header" id="from0_3">3</td><td nowrap="nowrap">three</td><td class="diff_next"></td><td class="diff_header" id="to0_3">3</td><td nowrap="nowrap">three</td></tr>
+        </tbody>        
+        <tbody>
+            <tr><td class="diff_next" id="difflib_chg_to0__1"></td><td class="diff_header" id="from0_10">10</td><td nowrap="nowrap">ten</td><td class="diff_next"></td><td class="diff_header" id="to0_10">10</td><td nowrap="nowrap">ten</td></tr>
+            <tr><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header"></td><td nowrap="nowrap"></td><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="to0_11">11</td><td nowrap="nowrap"><span class="diff_add">eleven</span></td></tr>
+        </tbody>
+    </table>"##
+    );
+}
+
+#[test]
+fn test_html_diff_matches_python_tabs() {
+    let mut html_diff = HtmlDiff::new();
+    html_diff.tab_size = 4;
+    let result = html_diff.make_table(
+        &["\tindented\n", "a\tb\n", "same\n"],
+        &["\tindented!\n", "a\tc\n", "same\n"],
+        "",
+        "",
+        false,
+        5,
+    );
+    assert_eq!(
+        with_first_prefix(result),
+        r##"
+    <table class="diff" id="difflib_chg_to0__top"
+           cellspacing="0" cellpadding="0" rules="groups" >
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        
+        <tbody>
+            <tr><td class="diff_next" id="difflib_chg_to0__0"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="from0_1">1</td><td nowrap="nowrap">&nbsp;&nbsp;&nbsp;&nbsp;indented</td><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="to0_1">1</td><td nowrap="nowrap">&nbsp;&nbsp;&nbsp;&nbsp;indented<span class="diff_add">!</span></td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header" id="from0_2">2</td><td nowrap="nowrap">a&nbsp;&nbsp;&nbsp;<span class="diff_chg">b</span></td><td class="diff_next"></td><td class="diff_header" id="to0_2">2</td><td nowrap="nowrap">a&nbsp;&nbsp;&nbsp;<span class="diff_chg">c</span></td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header" id="from0_3">3</td><td nowrap="nowrap">same</td><td class="diff_next"></td><td class="diff_header" id="to0_3">3</td><td nowrap="nowrap">same</td></tr>
+        </tbody>
+    </table>"##
+    );
+}
+
+#[test]
+fn test_html_diff_matches_python_wrap_column() {
+    let mut html_diff = HtmlDiff::new();
+    html_diff.wrap_column = Some(10);
+    let result = html_diff.make_table(
+        &["short\n", "a long line that needs wrapping\n", "x<y & z\n"],
+        &[
+            "short\n",
+            "a long line that needed wrapping\n",
+            "x>y & z\n",
+            "added line that is long too\n",
+        ],
+        "",
+        "",
+        false,
+        5,
+    );
+    assert_eq!(
+        with_first_prefix(result),
+        r##"
+    <table class="diff" id="difflib_chg_to0__top"
+           cellspacing="0" cellpadding="0" rules="groups" >
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        
+        <tbody>
+            <tr><td class="diff_next" id="difflib_chg_to0__0"><a href="#difflib_chg_to0__0">f</a></td><td class="diff_header" id="from0_1">1</td><td nowrap="nowrap">short</td><td class="diff_next"><a href="#difflib_chg_to0__0">f</a></td><td class="diff_header" id="to0_1">1</td><td nowrap="nowrap">short</td></tr>
+            <tr><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="from0_2">2</td><td nowrap="nowrap">a&nbsp;long&nbsp;lin</td><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="to0_2">2</td><td nowrap="nowrap">a&nbsp;long&nbsp;lin</td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap">e&nbsp;that&nbsp;nee</td><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap">e&nbsp;that&nbsp;nee</td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap">d<span class="diff_chg">s</span>&nbsp;wrappin</td><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap">d<span class="diff_chg">ed</span>&nbsp;wrappi</td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap">g</td><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap">ng</td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header" id="from0_3">3</td><td nowrap="nowrap">x<span class="diff_chg">&lt;</span>y&nbsp;&amp;&nbsp;z</td><td class="diff_next"></td><td class="diff_header" id="to0_3">3</td><td nowrap="nowrap">x<span class="diff_chg">&gt;</span>y&nbsp;&amp;&nbsp;z</td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header"></td><td nowrap="nowrap"></td><td class="diff_next"></td><td class="diff_header" id="to0_4">4</td><td nowrap="nowrap"><span class="diff_add">added&nbsp;line</span></td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header"></td><td nowrap="nowrap">&nbsp;</td><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap"><span class="diff_add">&nbsp;that&nbsp;is&nbsp;l</span></td></tr>
+            <tr><td class="diff_next"></td><td class="diff_header"></td><td nowrap="nowrap">&nbsp;</td><td class="diff_next"></td><td class="diff_header">></td><td nowrap="nowrap"><span class="diff_add">ong&nbsp;too</span></td></tr>
+        </tbody>
+    </table>"##
+    );
+}
+
+#[test]
+fn test_html_diff_matches_python_file() {
+    let result = HtmlDiff::new().make_file(
+        &["same\n", "old\n"],
+        &["same\n", "new\n"],
+        "a.txt",
+        "b.txt",
+        false,
+        5,
+    );
+    assert_eq!(
+        with_first_prefix(result),
+        r##"
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN"
+          "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd">
+
+<html>
+
+<head>
+    <meta http-equiv="Content-Type"
+          content="text/html; charset=utf-8" />
+    <title></title>
+    <style type="text/css">
+        table.diff {font-family:Courier; border:medium;}
+        .diff_header {background-color:#e0e0e0}
+        td.diff_header {text-align:right}
+        .diff_next {background-color:#c0c0c0}
+        .diff_add {background-color:#aaffaa}
+        .diff_chg {background-color:#ffff77}
+        .diff_sub {background-color:#ffaaaa}
+    </style>
+</head>
+
+<body>
+    
+    <table class="diff" id="difflib_chg_to0__top"
+           cellspacing="0" cellpadding="0" rules="groups" >
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <colgroup></colgroup> <colgroup></colgroup> <colgroup></colgroup>
+        <thead><tr><th class="diff_next"><br /></th><th colspan="2" class="diff_header">a.txt</th><th class="diff_next"><br /></th><th colspan="2" class="diff_header">b.txt</th></tr></thead>
+        <tbody>
+            <tr><td class="diff_next" id="difflib_chg_to0__0"><a href="#difflib_chg_to0__0">f</a></td><td class="diff_header" id="from0_1">1</td><td nowrap="nowrap">same</td><td class="diff_next"><a href="#difflib_chg_to0__0">f</a></td><td class="diff_header" id="to0_1">1</td><td nowrap="nowrap">same</td></tr>
+            <tr><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="from0_2">2</td><td nowrap="nowrap"><span class="diff_sub">old</span></td><td class="diff_next"><a href="#difflib_chg_to0__top">t</a></td><td class="diff_header" id="to0_2">2</td><td nowrap="nowrap"><span class="diff_add">new</span></td></tr>
+        </tbody>
+    </table>
+    <table class="diff" summary="Legends">
+        <tr> <th colspan="2"> Legends </th> </tr>
+        <tr> <td> <table border="" summary="Colors">
+                      <tr><th> Colors </th> </tr>
+                      <tr><td class="diff_add">&nbsp;Added&nbsp;</td></tr>
+                      <tr><td class="diff_chg">Changed</td> </tr>
+                      <tr><td class="diff_sub">Deleted</td> </tr>
+                  </table></td>
+             <td> <table border="" summary="Links">
+                      <tr><th colspan="2"> Links </th> </tr>
+                      <tr><td>(f)irst change</td> </tr>
+                      <tr><td>(n)ext change</td> </tr>
+                      <tr><td>(t)op</td> </tr>
+                  </table></td> </tr>
+    </table>
+</body>
+
+</html>"##
+    );
+}